@@ -0,0 +1,392 @@
+// Claude accounts monitored side by side. Each slot has its own credential
+// source, usage, errors and history; the tray shows one of them.
+//
+// With no [[accounts]] in settings there is a single slot reading Claude
+// Code's own login, which is how the app behaved before slots existed.
+
+use crate::credentials::CredentialSource;
+use crate::settings::Settings;
+use crate::status::Failure;
+use crate::subscription::SubscriptionTier;
+use crate::{AccountInfo, UsageResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+pub const DEFAULT_ACCOUNT_ID: &str = "default";
+pub const APP_ACCOUNT_ID: &str = "app";
+
+// A week of samples at the shortest polling interval
+const HISTORY_LIMIT: usize = 7 * 24 * 60 * 2;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AccountSettings {
+    // Stable id used by commands and events, e.g. "personal"
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub credentials: CredentialSource,
+}
+
+impl Default for AccountSettings {
+    fn default() -> Self {
+        Self {
+            id: DEFAULT_ACCOUNT_ID.to_string(),
+            name: "Claude".to_string(),
+            credentials: CredentialSource::ClaudeCode,
+        }
+    }
+}
+
+// Which account the tray title and icon follow
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TrayAccount {
+    #[default]
+    Active,
+    // Whichever account is closest to a limit
+    MostConstrained,
+}
+
+pub fn validate_accounts(accounts: &[AccountSettings]) -> Result<(), String> {
+    for (i, account) in accounts.iter().enumerate() {
+        if account.id.is_empty() {
+            return Err("account id must not be empty".to_string());
+        }
+        if accounts[..i].iter().any(|other| other.id == account.id) {
+            return Err(format!("account id '{}' is used more than once", account.id));
+        }
+        account
+            .credentials
+            .validate()
+            .map_err(|e| format!("account '{}': {}", account.id, e))?;
+    }
+    Ok(())
+}
+
+pub fn configured(settings: &Settings) -> Vec<AccountSettings> {
+    if settings.accounts.is_empty() {
+        vec![AccountSettings::default()]
+    } else {
+        settings.accounts.clone()
+    }
+}
+
+// The account that reads the app's own login, if any
+pub fn app_account(settings: &Settings) -> Option<AccountSettings> {
+    configured(settings).into_iter().find(|account| account.credentials == CredentialSource::App)
+}
+
+// After signing in from the app, makes sure an account reads that login.
+// Without configured accounts it takes the place of the implicit Claude Code
+// one, unless Claude Code is signed in as well
+pub fn add_app_account(settings: &mut Settings, keep_claude_code: bool) {
+    if app_account(settings).is_some() {
+        return;
+    }
+    if settings.accounts.is_empty() && keep_claude_code {
+        settings.accounts.push(AccountSettings::default());
+    }
+    settings.accounts.push(AccountSettings {
+        id: APP_ACCOUNT_ID.to_string(),
+        name: "Claude (app login)".to_string(),
+        credentials: CredentialSource::App,
+    });
+    settings.active_account = Some(APP_ACCOUNT_ID.to_string());
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HistoryEntry {
+    Usage {
+        at: DateTime<Utc>,
+        five_hour: Option<f64>,
+        seven_day: Option<f64>,
+    },
+    SubscriptionChanged {
+        at: DateTime<Utc>,
+        from: Option<SubscriptionTier>,
+        to: Option<SubscriptionTier>,
+    },
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AccountState {
+    pub id: String,
+    pub name: String,
+    pub usage: Option<UsageResponse>,
+    pub account: Option<AccountInfo>,
+    pub last_error: Option<String>,
+    pub last_updated: Option<DateTime<Utc>>,
+    // When usage was last fetched or tried to be, and when the poller next will
+    pub last_attempt: Option<DateTime<Utc>>,
+    pub next_poll: Option<DateTime<Utc>>,
+    // Why the last fetch failed and how many in a row have; see status.rs
+    #[serde(skip)]
+    pub failure: Option<Failure>,
+    #[serde(skip)]
+    pub failures: u32,
+    // False once the account's credentials are gone, e.g. after `claude /logout`
+    pub logged_in: bool,
+    // Served separately by get_history; too large to send with every update
+    #[serde(skip)]
+    pub history: VecDeque<HistoryEntry>,
+}
+
+impl AccountState {
+    pub fn new(settings: &AccountSettings) -> Self {
+        Self {
+            id: settings.id.clone(),
+            name: settings.name.clone(),
+            usage: None,
+            account: None,
+            last_error: None,
+            last_updated: None,
+            last_attempt: None,
+            next_poll: None,
+            failure: None,
+            failures: 0,
+            logged_in: true,
+            history: VecDeque::new(),
+        }
+    }
+
+    fn push_history(&mut self, entry: HistoryEntry) {
+        self.history.push_back(entry);
+        while self.history.len() > HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+    }
+
+    // Stores a freshly fetched profile. Returns whether the subscription tier
+    // changed, which is also recorded in history; the first profile fetched
+    // isn't a change
+    pub fn record_account(&mut self, info: AccountInfo) -> bool {
+        let changed = match self.account.take() {
+            // Logged in as someone else: earlier entries aren't theirs
+            Some(previous) if previous.email.is_some() && previous.email != info.email => {
+                self.history.clear();
+                false
+            }
+            Some(previous) if previous.subscription != info.subscription => {
+                self.push_history(HistoryEntry::SubscriptionChanged {
+                    at: Utc::now(),
+                    from: previous.subscription,
+                    to: info.subscription.clone(),
+                });
+                true
+            }
+            _ => false,
+        };
+        self.account = Some(info);
+        changed
+    }
+
+    // Forgets everything about the account that was logged in
+    pub fn log_out(&mut self) {
+        *self = Self {
+            id: std::mem::take(&mut self.id),
+            name: std::mem::take(&mut self.name),
+            logged_in: false,
+            ..Self::new(&AccountSettings::default())
+        };
+    }
+
+    pub fn record_usage(&mut self, usage: UsageResponse) {
+        let now = Utc::now();
+        let utilization = |limit: &Option<crate::UsageLimit>| limit.as_ref().and_then(|l| l.utilization);
+        self.push_history(HistoryEntry::Usage {
+            at: now,
+            five_hour: utilization(&usage.five_hour),
+            seven_day: utilization(&usage.seven_day),
+        });
+        self.usage = Some(usage);
+        self.last_error = None;
+        self.last_updated = Some(now);
+        self.last_attempt = Some(now);
+        self.failure = None;
+        self.failures = 0;
+    }
+
+    pub fn record_failure(&mut self, error: String, failure: Failure) {
+        self.last_error = Some(error);
+        self.last_attempt = Some(Utc::now());
+        self.failure = Some(failure);
+        self.failures += 1;
+    }
+
+    // Highest utilization across the 5-hour and 7-day limits
+    fn peak_utilization(&self) -> Option<f64> {
+        let usage = self.usage.as_ref()?;
+        [&usage.five_hour, &usage.seven_day]
+            .iter()
+            .filter_map(|limit| limit.as_ref().and_then(|l| l.utilization))
+            .reduce(f64::max)
+    }
+}
+
+// Rebuilds slots for a new account list, keeping the state of accounts
+// that are still configured
+pub fn reconcile(current: &[AccountState], configured: &[AccountSettings]) -> Vec<AccountState> {
+    configured
+        .iter()
+        .map(|settings| match current.iter().find(|state| state.id == settings.id) {
+            Some(state) => AccountState { name: settings.name.clone(), ..state.clone() },
+            None => AccountState::new(settings),
+        })
+        .collect()
+}
+
+// Id of the account commands act on when none is given
+pub fn active_id(settings: &Settings) -> String {
+    let configured = configured(settings);
+    settings
+        .active_account
+        .as_ref()
+        .filter(|id| configured.iter().any(|account| &account.id == *id))
+        .cloned()
+        .unwrap_or_else(|| configured[0].id.clone())
+}
+
+// The account the tray shows
+pub fn displayed<'a>(accounts: &'a [AccountState], settings: &Settings) -> Option<&'a AccountState> {
+    let active = active_id(settings);
+    let most_constrained = || {
+        accounts
+            .iter()
+            .filter(|account| account.peak_utilization().is_some())
+            .max_by(|a, b| a.peak_utilization().partial_cmp(&b.peak_utilization()).unwrap_or(std::cmp::Ordering::Equal))
+    };
+    match settings.tray.account {
+        TrayAccount::MostConstrained => most_constrained(),
+        TrayAccount::Active => None,
+    }
+    .or_else(|| accounts.iter().find(|account| account.id == active))
+    .or_else(|| accounts.first())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UsageLimit;
+
+    fn account(id: &str) -> AccountSettings {
+        AccountSettings { id: id.to_string(), name: id.to_uppercase(), credentials: CredentialSource::ClaudeCode }
+    }
+
+    fn with_usage(settings: &AccountSettings, five_hour: f64) -> AccountState {
+        let mut state = AccountState::new(settings);
+        state.record_usage(UsageResponse {
+            five_hour: Some(UsageLimit { utilization: Some(five_hour), resets_at: None }),
+            ..UsageResponse::default()
+        });
+        state
+    }
+
+    fn ids(slots: &[AccountState]) -> Vec<&str> {
+        slots.iter().map(|slot| slot.id.as_str()).collect()
+    }
+
+    #[test]
+    fn reconcile_keeps_drops_and_adds_slots() {
+        let current = vec![with_usage(&account("personal"), 40.0), with_usage(&account("old"), 10.0)];
+        let renamed = AccountSettings { name: "Mine".to_string(), ..account("personal") };
+
+        let slots = reconcile(&current, &[account("work"), renamed]);
+        assert_eq!(ids(&slots), ["work", "personal"]);
+        assert!(slots[0].usage.is_none());
+        assert_eq!(slots[1].name, "Mine");
+        assert!(slots[1].usage.is_some());
+        assert_eq!(slots[1].history.len(), 1);
+
+        assert!(reconcile(&current, &[]).is_empty());
+    }
+
+    #[test]
+    fn active_id_falls_back_to_the_first_account() {
+        let mut settings = Settings::default();
+        assert_eq!(active_id(&settings), DEFAULT_ACCOUNT_ID);
+
+        settings.accounts = vec![account("personal"), account("work")];
+        assert_eq!(active_id(&settings), "personal");
+        settings.active_account = Some("work".to_string());
+        assert_eq!(active_id(&settings), "work");
+        // Removed from settings since it was chosen
+        settings.active_account = Some("gone".to_string());
+        assert_eq!(active_id(&settings), "personal");
+    }
+
+    #[test]
+    fn displayed_account() {
+        let mut settings = Settings {
+            accounts: vec![account("personal"), account("work")],
+            active_account: Some("work".to_string()),
+            ..Settings::default()
+        };
+        let slots = vec![with_usage(&account("personal"), 90.0), with_usage(&account("work"), 20.0)];
+        let displayed_id = |slots: &[AccountState], settings: &Settings| displayed(slots, settings).map(|slot| slot.id.clone());
+
+        assert_eq!(displayed_id(&slots, &settings).as_deref(), Some("work"));
+        settings.tray.account = TrayAccount::MostConstrained;
+        assert_eq!(displayed_id(&slots, &settings).as_deref(), Some("personal"));
+
+        // Without usage to compare, the active account
+        let fresh: Vec<_> = settings.accounts.iter().map(AccountState::new).collect();
+        assert_eq!(displayed_id(&fresh, &settings).as_deref(), Some("work"));
+
+        // Slots not yet reconciled with the settings: the first one
+        let stale = vec![AccountState::new(&account("old"))];
+        settings.tray.account = TrayAccount::Active;
+        assert_eq!(displayed_id(&stale, &settings).as_deref(), Some("old"));
+        assert_eq!(displayed_id(&[], &settings), None);
+    }
+
+    fn info(email: &str, subscription: SubscriptionTier) -> AccountInfo {
+        AccountInfo { email: Some(email.to_string()), subscription: Some(subscription), ..AccountInfo::default() }
+    }
+
+    #[test]
+    fn record_account_reports_tier_changes() {
+        let mut slot = with_usage(&account("personal"), 40.0);
+        // The first profile fetched isn't a change
+        assert!(!slot.record_account(info("me@example.com", SubscriptionTier::Pro)));
+        assert!(!slot.record_account(info("me@example.com", SubscriptionTier::Pro)));
+        assert_eq!(slot.history.len(), 1);
+
+        let max = SubscriptionTier::Max { multiplier: Some(5) };
+        assert!(slot.record_account(info("me@example.com", max.clone())));
+        assert!(matches!(
+            slot.history.back(),
+            Some(HistoryEntry::SubscriptionChanged { from: Some(SubscriptionTier::Pro), to: Some(to), .. }) if *to == max
+        ));
+        assert_eq!(slot.account.as_ref().and_then(|a| a.subscription.clone()), Some(max));
+    }
+
+    #[test]
+    fn record_account_clears_history_for_another_email() {
+        let mut slot = with_usage(&account("personal"), 40.0);
+        slot.record_account(info("me@example.com", SubscriptionTier::Pro));
+
+        // A different tier under another email isn't a change of this account's
+        assert!(!slot.record_account(info("other@example.com", SubscriptionTier::Team)));
+        assert!(slot.history.is_empty());
+        assert_eq!(slot.account.and_then(|a| a.email).as_deref(), Some("other@example.com"));
+    }
+
+    #[test]
+    fn signing_in_after_signing_out_keeps_the_app_account() {
+        let mut settings = Settings::default();
+        add_app_account(&mut settings, false);
+        let account = app_account(&settings).unwrap();
+        assert_eq!(account.id, APP_ACCOUNT_ID);
+        let mut slot = AccountState::new(&account);
+        slot.log_out();
+
+        // Signing in again changes nothing in the settings, so the existing
+        // account is the one to check
+        let signed_out = settings.clone();
+        add_app_account(&mut settings, true);
+        assert_eq!(settings, signed_out);
+        assert_eq!(app_account(&settings).map(|account| account.id), Some(slot.id));
+    }
+}
@@ -0,0 +1,256 @@
+// Launch at login: a LaunchAgent on macOS, and either an XDG autostart entry
+// or a systemd user unit on Linux.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const APP_ID: &str = "com.ariel.claude-usage-monitor";
+const APP_NAME: &str = "claude-usage-monitor";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AutostartMethod {
+    LaunchAgent,
+    XdgAutostart,
+    Systemd,
+}
+
+impl Default for AutostartMethod {
+    fn default() -> Self {
+        if cfg!(target_os = "macos") {
+            AutostartMethod::LaunchAgent
+        } else {
+            AutostartMethod::XdgAutostart
+        }
+    }
+}
+
+impl AutostartMethod {
+    fn name(self) -> &'static str {
+        match self {
+            AutostartMethod::LaunchAgent => "launch_agent",
+            AutostartMethod::XdgAutostart => "xdg_autostart",
+            AutostartMethod::Systemd => "systemd",
+        }
+    }
+
+    fn is_supported(self) -> bool {
+        match self {
+            AutostartMethod::LaunchAgent => cfg!(target_os = "macos"),
+            AutostartMethod::XdgAutostart | AutostartMethod::Systemd => !cfg!(target_os = "macos"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct AutostartSettings {
+    pub enabled: bool,
+    pub method: AutostartMethod,
+    // Whether the first-launch prompt has been answered
+    pub prompted: bool,
+}
+
+impl AutostartSettings {
+    // A LaunchAgent means nothing on Linux, nor a systemd unit on macOS
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.method.is_supported() {
+            return Err(format!("method '{}' is not available on this system", self.method.name()));
+        }
+        Ok(())
+    }
+}
+
+fn home_dir() -> PathBuf {
+    PathBuf::from(std::env::var("HOME").unwrap_or_default())
+}
+
+fn xdg_config_dir() -> PathBuf {
+    std::env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home_dir().join(".config"))
+}
+
+fn entry_path(method: AutostartMethod) -> PathBuf {
+    entry_path_in(method, &home_dir(), &xdg_config_dir())
+}
+
+fn entry_path_in(method: AutostartMethod, home: &Path, config_dir: &Path) -> PathBuf {
+    match method {
+        AutostartMethod::LaunchAgent => home
+            .join("Library/LaunchAgents")
+            .join(format!("{}.plist", APP_ID)),
+        AutostartMethod::XdgAutostart => config_dir
+            .join("autostart")
+            .join(format!("{}.desktop", APP_NAME)),
+        AutostartMethod::Systemd => config_dir
+            .join("systemd/user")
+            .join(format!("{}.service", APP_NAME)),
+    }
+}
+
+fn plist_string(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// Exec= quoting from the Desktop Entry spec: inside the quotes ", `, $ and \
+// take a backslash and % is doubled, then backslashes are escaped again as
+// in any string value
+fn desktop_exec(exe: &str) -> String {
+    let mut quoted = String::new();
+    for c in exe.chars() {
+        match c {
+            '"' | '`' | '$' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            c => quoted.push(c),
+        }
+    }
+    format!("\"{}\"", quoted.replace('\\', "\\\\"))
+}
+
+// ExecStart= quoting: C-style escapes inside the quotes, and $ and % doubled
+// so they aren't expanded as variables or specifiers
+fn systemd_exec(exe: &str) -> String {
+    let quoted = exe.replace('\\', "\\\\").replace('"', "\\\"").replace('$', "$$").replace('%', "%%");
+    format!("\"{}\"", quoted)
+}
+
+fn entry_contents(method: AutostartMethod, exe: &str) -> String {
+    match method {
+        AutostartMethod::LaunchAgent => format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+</dict>
+</plist>
+"#,
+            APP_ID,
+            plist_string(exe)
+        ),
+        AutostartMethod::XdgAutostart => format!(
+            "[Desktop Entry]\nType=Application\nName=Claude Usage Monitor\nExec={}\nX-GNOME-Autostart-enabled=true\n",
+            desktop_exec(exe)
+        ),
+        AutostartMethod::Systemd => format!(
+            "[Unit]\nDescription=Claude Usage Monitor\nPartOf=graphical-session.target\nAfter=graphical-session.target\n\n\
+             [Service]\nExecStart={}\nRestart=on-failure\n\n\
+             [Install]\nWantedBy=graphical-session.target\n",
+            systemd_exec(exe)
+        ),
+    }
+}
+
+fn systemctl(args: &[&str]) -> Result<(), String> {
+    let output = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run systemctl: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("systemctl {} failed: {}", args.join(" "), stderr.trim()));
+    }
+    Ok(())
+}
+
+pub fn is_installed(method: AutostartMethod) -> bool {
+    entry_path(method).exists()
+}
+
+// Writes the entry for the current executable, replacing any stale path from
+// a previous install location
+pub fn enable(method: AutostartMethod) -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| format!("Failed to locate executable: {}", e))?;
+    let path = entry_path(method);
+    let contents = entry_contents(method, &exe.to_string_lossy());
+    if std::fs::read_to_string(&path).is_ok_and(|existing| existing == contents) {
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    std::fs::write(&path, contents)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    if method == AutostartMethod::Systemd {
+        systemctl(&["daemon-reload"])?;
+        systemctl(&["enable", &format!("{}.service", APP_NAME)])?;
+    }
+    Ok(())
+}
+
+pub fn disable(method: AutostartMethod) -> Result<(), String> {
+    let path = entry_path(method);
+    if !path.exists() {
+        return Ok(());
+    }
+
+    if method == AutostartMethod::Systemd {
+        systemctl(&["disable", &format!("{}.service", APP_NAME)])?;
+    }
+    std::fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_paths() {
+        let (home, config) = (Path::new("/home/me"), Path::new("/home/me/.config"));
+        let path = |method| entry_path_in(method, home, config);
+        assert_eq!(
+            path(AutostartMethod::LaunchAgent),
+            Path::new("/home/me/Library/LaunchAgents/com.ariel.claude-usage-monitor.plist")
+        );
+        assert_eq!(
+            path(AutostartMethod::XdgAutostart),
+            Path::new("/home/me/.config/autostart/claude-usage-monitor.desktop")
+        );
+        assert_eq!(
+            path(AutostartMethod::Systemd),
+            Path::new("/home/me/.config/systemd/user/claude-usage-monitor.service")
+        );
+    }
+
+    #[test]
+    fn entries_run_the_executable() {
+        let exe = "/opt/Claude Usage Monitor/claude-usage-monitor";
+        let plist = entry_contents(AutostartMethod::LaunchAgent, exe);
+        assert!(plist.contains("<string>com.ariel.claude-usage-monitor</string>"));
+        assert!(plist.contains(&format!("<string>{}</string>", exe)));
+        let desktop = entry_contents(AutostartMethod::XdgAutostart, exe);
+        assert!(desktop.contains(&format!("\nExec=\"{}\"\n", exe)), "{}", desktop);
+        let unit = entry_contents(AutostartMethod::Systemd, exe);
+        assert!(unit.contains(&format!("\nExecStart=\"{}\"\n", exe)), "{}", unit);
+    }
+
+    #[test]
+    fn entries_escape_the_executable_path() {
+        let exe = r#"/opt/a "b" `c` $d \e 100%/<app> & co"#;
+        let plist = entry_contents(AutostartMethod::LaunchAgent, exe);
+        assert!(plist.contains(r#"<string>/opt/a "b" `c` $d \e 100%/&lt;app&gt; &amp; co</string>"#), "{}", plist);
+        let desktop = entry_contents(AutostartMethod::XdgAutostart, exe);
+        assert!(desktop.contains(r#"Exec="/opt/a \\"b\\" \\`c\\` \\$d \\\\e 100%%/<app> & co""#), "{}", desktop);
+        let unit = entry_contents(AutostartMethod::Systemd, exe);
+        assert!(unit.contains(r#"ExecStart="/opt/a \"b\" `c` $$d \\e 100%%/<app> & co""#), "{}", unit);
+    }
+}
@@ -0,0 +1,178 @@
+// Installed browsers and their profiles, so links open in the profile that is
+// signed in to the Claude account being monitored.
+//
+// Chromium-family browsers (Chrome, Brave, Edge, Arc, Chromium, Vivaldi)
+// share one implementation that differs only in where the user data lives
+// and which executable to launch. Firefox keeps its profiles in profiles.ini.
+// On Linux, Flatpak and Snap installs keep their data in sandboxed locations
+// and are listed as browsers of their own.
+
+mod chromium;
+mod firefox;
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Used when settings name a profile but no browser, as written by releases
+// that only supported Chrome
+pub const DEFAULT_BROWSER: &str = "chrome";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BrowserProfile {
+    // Id of the browser this profile belongs to
+    pub browser: String,
+    pub browser_name: String,
+    pub id: String,
+    pub name: String,
+    pub email: Option<String>,
+    // Chromium avatar resource, e.g. "chrome://theme/IDR_PROFILE_AVATAR_26"
+    pub avatar: Option<String>,
+    // Unix seconds; profiles of a browser are listed most recently used first
+    pub last_used: Option<i64>,
+}
+
+pub trait BrowserProvider {
+    // Stable identifier stored in settings, e.g. "chrome" or "firefox"
+    fn id(&self) -> &str;
+    fn is_installed(&self) -> bool;
+    fn profiles(&self) -> Vec<BrowserProfile>;
+    // `profile` is an id from `profiles()`; None lets the browser pick
+    fn open_url(&self, profile: Option<&str>, url: &str) -> Result<(), String>;
+}
+
+fn home_dir() -> PathBuf {
+    dirs::home_dir().unwrap_or_default()
+}
+
+// How to start a browser
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+enum Launcher {
+    // Executable at a fixed location, e.g. inside an .app bundle
+    Path(PathBuf),
+    // First of these executable names found on PATH
+    OnPath(&'static [&'static str]),
+    // Flatpak app id, run with `flatpak run`
+    Flatpak(&'static str),
+}
+
+fn find_on_path(name: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+fn flatpak_installed(app_id: &str, home: &Path) -> bool {
+    [PathBuf::from("/var/lib/flatpak/app"), home.join(".local/share/flatpak/app")]
+        .iter()
+        .any(|dir| dir.join(app_id).exists())
+}
+
+impl Launcher {
+    // None if the browser isn't installed
+    fn command(&self) -> Option<Command> {
+        match self {
+            Launcher::Path(path) => path.is_file().then(|| Command::new(path)),
+            Launcher::OnPath(names) => names.iter().find_map(|name| find_on_path(name)).map(Command::new),
+            Launcher::Flatpak(app_id) => {
+                if !flatpak_installed(app_id, &home_dir()) {
+                    return None;
+                }
+                let mut command = Command::new(find_on_path("flatpak")?);
+                command.args(["run", app_id]);
+                Some(command)
+            }
+        }
+    }
+}
+
+// Only https links to allowed hosts are opened, so a compromised webview
+// can't use open_url to pass flags or arbitrary URLs to a browser. Returns
+// the URL in normalized form, which never starts with '-'.
+pub fn check_url(url: &str, allowed_hosts: &[String]) -> Result<String, String> {
+    if url.trim_start().starts_with('-') {
+        return Err(format!("Refusing to open '{}': looks like a command-line flag", url));
+    }
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+    if parsed.scheme() != "https" {
+        return Err(format!("Refusing to open '{}': only https URLs are allowed", url));
+    }
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        return Err(format!("Refusing to open '{}': URLs with credentials are not allowed", url));
+    }
+    let host = parsed.host_str().unwrap_or_default();
+    if !allowed_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(host)) {
+        return Err(format!("Refusing to open '{}': {} is not an allowed host", url, host));
+    }
+    Ok(parsed.to_string())
+}
+
+pub fn providers() -> Vec<Box<dyn BrowserProvider + Send>> {
+    let home = home_dir();
+    let mut providers: Vec<Box<dyn BrowserProvider + Send>> = Vec::new();
+    for browser in chromium::browsers(&home) {
+        providers.push(Box::new(browser));
+    }
+    for browser in firefox::browsers(&home) {
+        providers.push(Box::new(browser));
+    }
+    providers
+}
+
+pub fn installed() -> Vec<Box<dyn BrowserProvider + Send>> {
+    providers().into_iter().filter(|p| p.is_installed()).collect()
+}
+
+pub fn find(id: &str) -> Option<Box<dyn BrowserProvider + Send>> {
+    installed().into_iter().find(|p| p.id() == id)
+}
+
+pub fn all_profiles() -> Vec<BrowserProfile> {
+    installed().iter().flat_map(|p| p.profiles()).collect()
+}
+
+// Profiles of `preferred` are checked first so a user who picked a browser
+// stays in it when several browsers are signed in to the same account
+pub fn find_profile_by_email(email: &str, preferred: Option<&str>) -> Option<BrowserProfile> {
+    let mut profiles = all_profiles();
+    profiles.sort_by_key(|p| Some(p.browser.as_str()) != preferred);
+    profiles
+        .into_iter()
+        .find(|p| p.email.as_deref().is_some_and(|e| e.eq_ignore_ascii_case(email)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fresh, empty stand-in for a home directory
+    pub fn test_home(name: &str) -> PathBuf {
+        let home = std::env::temp_dir().join(format!("claude-usage-monitor-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(&home).unwrap();
+        home
+    }
+
+    #[test]
+    fn check_url_allows_only_https_to_allowed_hosts() {
+        let allowed = vec!["claude.ai".to_string(), "console.anthropic.com".to_string()];
+        let cases = [
+            ("https://claude.ai/settings/usage", true),
+            ("https://CLAUDE.ai/", true),
+            ("https://console.anthropic.com/", true),
+            ("http://claude.ai/", false),
+            ("https://claude.ai.example.com/", false),
+            ("https://example.com/?claude.ai", false),
+            ("https://user@claude.ai/", false),
+            ("file:///etc/passwd", false),
+            ("javascript:alert(1)", false),
+            ("--user-data-dir=/tmp/x", false),
+            (" --incognito", false),
+            ("claude.ai", false),
+        ];
+        for (url, ok) in cases {
+            assert_eq!(check_url(url, &allowed).is_ok(), ok, "{}", url);
+        }
+    }
+}
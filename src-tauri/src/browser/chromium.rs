@@ -0,0 +1,363 @@
+// Chromium-family browsers. Each profile is a directory under the browser's
+// user data directory. The "Local State" file in that directory caches every
+// profile's name, avatar, signed-in account and last use, which saves reading
+// each profile's Preferences file (often megabytes) for the same details.
+
+use super::{BrowserProfile, BrowserProvider, Launcher};
+use std::path::{Path, PathBuf};
+
+pub struct ChromiumBrowser {
+    id: &'static str,
+    name: &'static str,
+    user_data_dir: PathBuf,
+    launcher: Launcher,
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn macos_browsers(home: &Path) -> Vec<ChromiumBrowser> {
+    let support = home.join("Library/Application Support");
+    let app = |bundle: &str, binary: &str| {
+        Launcher::Path(PathBuf::from(format!("/Applications/{}.app/Contents/MacOS/{}", bundle, binary)))
+    };
+    vec![
+        ChromiumBrowser {
+            id: "chrome",
+            name: "Google Chrome",
+            user_data_dir: support.join("Google/Chrome"),
+            launcher: app("Google Chrome", "Google Chrome"),
+        },
+        ChromiumBrowser {
+            id: "brave",
+            name: "Brave",
+            user_data_dir: support.join("BraveSoftware/Brave-Browser"),
+            launcher: app("Brave Browser", "Brave Browser"),
+        },
+        ChromiumBrowser {
+            id: "edge",
+            name: "Microsoft Edge",
+            user_data_dir: support.join("Microsoft Edge"),
+            launcher: app("Microsoft Edge", "Microsoft Edge"),
+        },
+        ChromiumBrowser {
+            id: "arc",
+            name: "Arc",
+            user_data_dir: support.join("Arc/User Data"),
+            launcher: app("Arc", "Arc"),
+        },
+        ChromiumBrowser {
+            id: "chromium",
+            name: "Chromium",
+            user_data_dir: support.join("Chromium"),
+            launcher: app("Chromium", "Chromium"),
+        },
+        ChromiumBrowser {
+            id: "vivaldi",
+            name: "Vivaldi",
+            user_data_dir: support.join("Vivaldi"),
+            launcher: app("Vivaldi", "Vivaldi"),
+        },
+    ]
+}
+
+#[cfg(any(not(target_os = "macos"), test))]
+fn linux_browsers(home: &Path) -> Vec<ChromiumBrowser> {
+    let config = home.join(".config");
+    let flatpak_data = |app_id: &str, dir: &str| home.join(".var/app").join(app_id).join("config").join(dir);
+    vec![
+        ChromiumBrowser {
+            id: "chrome",
+            name: "Google Chrome",
+            user_data_dir: config.join("google-chrome"),
+            launcher: Launcher::OnPath(&["google-chrome", "google-chrome-stable"]),
+        },
+        ChromiumBrowser {
+            id: "chrome-flatpak",
+            name: "Google Chrome (Flatpak)",
+            user_data_dir: flatpak_data("com.google.Chrome", "google-chrome"),
+            launcher: Launcher::Flatpak("com.google.Chrome"),
+        },
+        ChromiumBrowser {
+            id: "chromium",
+            name: "Chromium",
+            user_data_dir: config.join("chromium"),
+            launcher: Launcher::OnPath(&["chromium", "chromium-browser"]),
+        },
+        ChromiumBrowser {
+            id: "chromium-flatpak",
+            name: "Chromium (Flatpak)",
+            user_data_dir: flatpak_data("org.chromium.Chromium", "chromium"),
+            launcher: Launcher::Flatpak("org.chromium.Chromium"),
+        },
+        ChromiumBrowser {
+            id: "chromium-snap",
+            name: "Chromium (Snap)",
+            user_data_dir: home.join("snap/chromium/common/chromium"),
+            launcher: Launcher::Path(PathBuf::from("/snap/bin/chromium")),
+        },
+        ChromiumBrowser {
+            id: "brave",
+            name: "Brave",
+            user_data_dir: config.join("BraveSoftware/Brave-Browser"),
+            launcher: Launcher::OnPath(&["brave-browser", "brave"]),
+        },
+        ChromiumBrowser {
+            id: "brave-flatpak",
+            name: "Brave (Flatpak)",
+            user_data_dir: flatpak_data("com.brave.Browser", "BraveSoftware/Brave-Browser"),
+            launcher: Launcher::Flatpak("com.brave.Browser"),
+        },
+        ChromiumBrowser {
+            id: "edge",
+            name: "Microsoft Edge",
+            user_data_dir: config.join("microsoft-edge"),
+            launcher: Launcher::OnPath(&["microsoft-edge", "microsoft-edge-stable"]),
+        },
+        ChromiumBrowser {
+            id: "edge-flatpak",
+            name: "Microsoft Edge (Flatpak)",
+            user_data_dir: flatpak_data("com.microsoft.Edge", "microsoft-edge"),
+            launcher: Launcher::Flatpak("com.microsoft.Edge"),
+        },
+        ChromiumBrowser {
+            id: "vivaldi",
+            name: "Vivaldi",
+            user_data_dir: config.join("vivaldi"),
+            launcher: Launcher::OnPath(&["vivaldi", "vivaldi-stable"]),
+        },
+    ]
+}
+
+#[cfg(target_os = "macos")]
+pub fn browsers(home: &Path) -> Vec<ChromiumBrowser> {
+    macos_browsers(home)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn browsers(home: &Path) -> Vec<ChromiumBrowser> {
+    linux_browsers(home)
+}
+
+// Profile directories Chromium creates for itself
+const INTERNAL_PROFILES: [&str; 2] = ["System Profile", "Guest Profile"];
+
+impl ChromiumBrowser {
+    fn profile(&self, id: &str, name: String, email: Option<String>) -> BrowserProfile {
+        BrowserProfile {
+            browser: self.id.to_string(),
+            browser_name: self.name.to_string(),
+            id: id.to_string(),
+            name,
+            email,
+            avatar: None,
+            last_used: None,
+        }
+    }
+
+    fn read_preferences(&self, dir_name: &str) -> Option<BrowserProfile> {
+        let content = std::fs::read_to_string(self.user_data_dir.join(dir_name).join("Preferences")).ok()?;
+        let json: serde_json::Value = serde_json::from_str(&content).ok()?;
+        let name = json["profile"]["name"].as_str().unwrap_or(dir_name).to_string();
+        let email = json["account_info"]
+            .as_array()
+            .and_then(|accounts| accounts.first())
+            .and_then(|account| account["email"].as_str())
+            .map(|email| email.to_string());
+        Some(self.profile(dir_name, name, email))
+    }
+
+    // None if Local State is missing or has no profiles
+    fn read_local_state(&self) -> Option<Vec<BrowserProfile>> {
+        let content = std::fs::read_to_string(self.user_data_dir.join("Local State")).ok()?;
+        let json: serde_json::Value = serde_json::from_str(&content).ok()?;
+        let info_cache = json["profile"]["info_cache"].as_object().filter(|cache| !cache.is_empty())?;
+
+        let profiles = info_cache
+            .iter()
+            .filter_map(|(id, info)| {
+                let name = info["name"].as_str().filter(|name| !name.is_empty());
+                let mut profile = match name {
+                    Some(name) => {
+                        let email = info["user_name"].as_str().filter(|email| !email.is_empty());
+                        self.profile(id, name.to_string(), email.map(|email| email.to_string()))
+                    }
+                    None => self.read_preferences(id)?,
+                };
+                profile.avatar = info["avatar_icon"].as_str().map(|avatar| avatar.to_string());
+                profile.last_used = info["active_time"].as_f64().map(|time| time as i64);
+                Some(profile)
+            })
+            .collect();
+        Some(profiles)
+    }
+
+    // Every directory with a Preferences file, for browsers without Local State
+    fn scan_profile_dirs(&self) -> Vec<BrowserProfile> {
+        let Ok(entries) = std::fs::read_dir(&self.user_data_dir) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| !INTERNAL_PROFILES.contains(&name.as_str()))
+            .filter_map(|name| self.read_preferences(&name))
+            .collect()
+    }
+
+    // The profile as a directory the browser has. Settings can be written
+    // from the webview, so nothing else is handed to --profile-directory
+    fn resolve_profile(&self, profile: &str) -> Result<String, String> {
+        self.profiles()
+            .into_iter()
+            .find(|known| known.id == profile)
+            .map(|known| known.id)
+            .ok_or_else(|| format!("'{}' is not a {} profile", profile, self.name))
+    }
+}
+
+impl BrowserProvider for ChromiumBrowser {
+    fn id(&self) -> &str {
+        self.id
+    }
+
+    fn is_installed(&self) -> bool {
+        self.launcher.command().is_some()
+    }
+
+    fn profiles(&self) -> Vec<BrowserProfile> {
+        let mut profiles = self.read_local_state().unwrap_or_else(|| self.scan_profile_dirs());
+        profiles.sort_by(|a, b| b.last_used.cmp(&a.last_used).then_with(|| a.id.cmp(&b.id)));
+        profiles
+    }
+
+    // Launching the binary directly makes the profile apply even when the
+    // browser is already running
+    fn open_url(&self, profile: Option<&str>, url: &str) -> Result<(), String> {
+        let profile = profile.map(|profile| self.resolve_profile(profile)).transpose()?;
+        let mut command = self
+            .launcher
+            .command()
+            .ok_or_else(|| format!("{} is not installed", self.name))?;
+        if let Some(profile) = profile {
+            command.arg(format!("--profile-directory={}", profile));
+        }
+        command
+            .arg(url)
+            .spawn()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to launch {}: {}", self.name, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::tests::test_home;
+
+    fn write_preferences(dir: &Path, name: &str, email: Option<&str>) {
+        std::fs::create_dir_all(dir).unwrap();
+        let accounts: Vec<_> = email.iter().map(|email| serde_json::json!({ "email": email })).collect();
+        let preferences = serde_json::json!({ "profile": { "name": name }, "account_info": accounts });
+        std::fs::write(dir.join("Preferences"), preferences.to_string()).unwrap();
+    }
+
+    fn profiles_of(browsers: &[ChromiumBrowser], id: &str) -> Vec<(String, String, Option<String>)> {
+        let browser = browsers.iter().find(|b| b.id == id).unwrap();
+        browser.profiles().into_iter().map(|p| (p.id, p.name, p.email)).collect()
+    }
+
+    #[test]
+    fn macos_layout() {
+        let home = test_home("chromium-macos");
+        let chrome = home.join("Library/Application Support/Google/Chrome");
+        write_preferences(&chrome.join("Default"), "Personal", Some("me@example.com"));
+        write_preferences(&chrome.join("Profile 2"), "Work", None);
+        // Not profile directories
+        std::fs::create_dir_all(chrome.join("Crashpad")).unwrap();
+        write_preferences(&chrome.join("System Profile"), "System", None);
+        write_preferences(&home.join("Library/Application Support/BraveSoftware/Brave-Browser/Default"), "Brave", None);
+
+        let browsers = macos_browsers(&home);
+        assert_eq!(
+            profiles_of(&browsers, "chrome"),
+            vec![
+                ("Default".to_string(), "Personal".to_string(), Some("me@example.com".to_string())),
+                ("Profile 2".to_string(), "Work".to_string(), None),
+            ]
+        );
+        assert_eq!(profiles_of(&browsers, "brave").len(), 1);
+        assert!(profiles_of(&browsers, "edge").is_empty());
+    }
+
+    #[test]
+    fn local_state() {
+        let home = test_home("chromium-local-state");
+        let chrome = home.join("Library/Application Support/Google/Chrome");
+        std::fs::create_dir_all(&chrome).unwrap();
+        let local_state = serde_json::json!({
+            "profile": {
+                "info_cache": {
+                    "Default": { "name": "Personal", "user_name": "", "avatar_icon": "chrome://theme/IDR_PROFILE_AVATAR_26", "active_time": 1700000000.5 },
+                    "Work Profile": { "name": "Work", "user_name": "work@example.com", "active_time": 1750000000.0 },
+                    // No cached name: read from Preferences instead
+                    "Profile 4": { "user_name": "" }
+                }
+            }
+        });
+        std::fs::write(chrome.join("Local State"), local_state.to_string()).unwrap();
+        write_preferences(&chrome.join("Profile 4"), "Side project", Some("side@example.com"));
+
+        let browsers = macos_browsers(&home);
+        let chrome = browsers.iter().find(|b| b.id == "chrome").unwrap();
+        let profiles = chrome.profiles();
+        assert_eq!(
+            profiles.iter().map(|p| (p.id.as_str(), p.name.as_str(), p.email.as_deref())).collect::<Vec<_>>(),
+            vec![
+                ("Work Profile", "Work", Some("work@example.com")),
+                ("Default", "Personal", None),
+                ("Profile 4", "Side project", Some("side@example.com")),
+            ]
+        );
+        assert_eq!(profiles[1].avatar.as_deref(), Some("chrome://theme/IDR_PROFILE_AVATAR_26"));
+        assert_eq!(profiles[1].last_used, Some(1700000000));
+    }
+
+    #[test]
+    fn linux_layout() {
+        let home = test_home("chromium-linux");
+        write_preferences(&home.join(".config/google-chrome/Default"), "Personal", Some("me@example.com"));
+        write_preferences(&home.join(".config/chromium/Profile 1"), "Chromium", None);
+        write_preferences(
+            &home.join(".var/app/com.google.Chrome/config/google-chrome/Profile 3"),
+            "Sandboxed",
+            Some("work@example.com"),
+        );
+        write_preferences(&home.join("snap/chromium/common/chromium/Default"), "Snap", None);
+
+        let browsers = linux_browsers(&home);
+        assert_eq!(
+            profiles_of(&browsers, "chrome"),
+            vec![("Default".to_string(), "Personal".to_string(), Some("me@example.com".to_string()))]
+        );
+        assert_eq!(
+            profiles_of(&browsers, "chrome-flatpak"),
+            vec![("Profile 3".to_string(), "Sandboxed".to_string(), Some("work@example.com".to_string()))]
+        );
+        assert_eq!(profiles_of(&browsers, "chromium")[0].1, "Chromium");
+        assert_eq!(profiles_of(&browsers, "chromium-snap")[0].1, "Snap");
+        assert!(profiles_of(&browsers, "brave").is_empty());
+    }
+
+    #[test]
+    fn only_existing_profiles_are_opened() {
+        let home = test_home("chromium-resolve");
+        write_preferences(&home.join(".config/google-chrome/Profile 2"), "Work", None);
+        let browsers = linux_browsers(&home);
+        let chrome = browsers.iter().find(|b| b.id == "chrome").unwrap();
+
+        assert_eq!(chrome.resolve_profile("Profile 2"), Ok("Profile 2".to_string()));
+        for profile in ["Default", "../Profile 2", "Profile 2 --incognito", ""] {
+            assert!(chrome.resolve_profile(profile).is_err(), "{}", profile);
+        }
+    }
+}
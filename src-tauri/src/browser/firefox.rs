@@ -0,0 +1,240 @@
+// Firefox lists its profiles in profiles.ini. A profile's Firefox account,
+// if signed in, is in signedInUser.json inside the profile directory.
+
+use super::{BrowserProfile, BrowserProvider, Launcher};
+use std::path::{Path, PathBuf};
+
+pub struct FirefoxBrowser {
+    id: &'static str,
+    name: &'static str,
+    // Directory containing profiles.ini
+    profiles_dir: PathBuf,
+    launcher: Launcher,
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn macos_browsers(home: &Path) -> Vec<FirefoxBrowser> {
+    vec![FirefoxBrowser {
+        id: "firefox",
+        name: "Firefox",
+        profiles_dir: home.join("Library/Application Support/Firefox"),
+        launcher: Launcher::Path(PathBuf::from("/Applications/Firefox.app/Contents/MacOS/firefox")),
+    }]
+}
+
+#[cfg(any(not(target_os = "macos"), test))]
+fn linux_browsers(home: &Path) -> Vec<FirefoxBrowser> {
+    vec![
+        FirefoxBrowser {
+            id: "firefox",
+            name: "Firefox",
+            profiles_dir: home.join(".mozilla/firefox"),
+            launcher: Launcher::OnPath(&["firefox"]),
+        },
+        FirefoxBrowser {
+            id: "firefox-flatpak",
+            name: "Firefox (Flatpak)",
+            profiles_dir: home.join(".var/app/org.mozilla.firefox/.mozilla/firefox"),
+            launcher: Launcher::Flatpak("org.mozilla.firefox"),
+        },
+        FirefoxBrowser {
+            id: "firefox-snap",
+            name: "Firefox (Snap)",
+            profiles_dir: home.join("snap/firefox/common/.mozilla/firefox"),
+            launcher: Launcher::Path(PathBuf::from("/snap/bin/firefox")),
+        },
+    ]
+}
+
+#[cfg(target_os = "macos")]
+pub fn browsers(home: &Path) -> Vec<FirefoxBrowser> {
+    macos_browsers(home)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn browsers(home: &Path) -> Vec<FirefoxBrowser> {
+    linux_browsers(home)
+}
+
+// One [ProfileN] section of profiles.ini
+struct IniProfile {
+    name: Option<String>,
+    path: Option<String>,
+    is_relative: bool,
+}
+
+fn parse_profiles_ini(content: &str) -> Vec<IniProfile> {
+    let mut profiles = Vec::new();
+    let mut current: Option<IniProfile> = None;
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            profiles.extend(current.take());
+            if line.starts_with("[Profile") {
+                current = Some(IniProfile { name: None, path: None, is_relative: true });
+            }
+        } else if let (Some(profile), Some((key, value))) = (current.as_mut(), line.split_once('=')) {
+            match key.trim() {
+                "Name" => profile.name = Some(value.trim().to_string()),
+                "Path" => profile.path = Some(value.trim().to_string()),
+                "IsRelative" => profile.is_relative = value.trim() != "0",
+                _ => {}
+            }
+        }
+    }
+    profiles.extend(current);
+    profiles
+}
+
+impl FirefoxBrowser {
+    fn profile_dir(&self, profile: &IniProfile) -> Option<PathBuf> {
+        let path = profile.path.as_ref()?;
+        Some(if profile.is_relative { self.profiles_dir.join(path) } else { PathBuf::from(path) })
+    }
+
+    // The profile as a directory listed in profiles.ini. Settings can be
+    // written from the webview, so nothing else is handed to --profile
+    fn resolve_profile(&self, profile: &str) -> Result<String, String> {
+        self.profiles()
+            .into_iter()
+            .find(|known| known.id == profile)
+            .map(|known| known.id)
+            .ok_or_else(|| format!("'{}' is not a {} profile", profile, self.name))
+    }
+
+    fn read_email(profile_dir: &Path) -> Option<String> {
+        let content = std::fs::read_to_string(profile_dir.join("signedInUser.json")).ok()?;
+        let json: serde_json::Value = serde_json::from_str(&content).ok()?;
+        json["accountData"]["email"].as_str().map(|email| email.to_string())
+    }
+}
+
+impl BrowserProvider for FirefoxBrowser {
+    fn id(&self) -> &str {
+        self.id
+    }
+
+    fn is_installed(&self) -> bool {
+        self.launcher.command().is_some()
+    }
+
+    // Profile ids are the profile directory's absolute path, which is what
+    // --profile takes
+    fn profiles(&self) -> Vec<BrowserProfile> {
+        let Ok(content) = std::fs::read_to_string(self.profiles_dir.join("profiles.ini")) else {
+            return Vec::new();
+        };
+        parse_profiles_ini(&content)
+            .iter()
+            .filter_map(|profile| {
+                let dir = self.profile_dir(profile)?;
+                Some(BrowserProfile {
+                    browser: self.id.to_string(),
+                    browser_name: self.name.to_string(),
+                    id: dir.to_string_lossy().to_string(),
+                    name: profile.name.clone().unwrap_or_else(|| dir.to_string_lossy().to_string()),
+                    email: Self::read_email(&dir),
+                    avatar: None,
+                    last_used: None,
+                })
+            })
+            .collect()
+    }
+
+    // An instance already running with the profile receives the URL as a new tab
+    fn open_url(&self, profile: Option<&str>, url: &str) -> Result<(), String> {
+        let profile = profile.map(|profile| self.resolve_profile(profile)).transpose()?;
+        let mut command = self
+            .launcher
+            .command()
+            .ok_or_else(|| format!("{} is not installed", self.name))?;
+        if let Some(profile) = profile {
+            command.arg("--profile").arg(profile);
+        }
+        command
+            .arg("--new-tab")
+            .arg(url)
+            .spawn()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to launch {}: {}", self.name, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::tests::test_home;
+
+    const PROFILES_INI: &str = "\
+[Install4F96D1932A9F858E]
+Default=abcd.default-release
+
+[Profile1]
+Name=work
+IsRelative=1
+Path=efgh.work
+
+[Profile0]
+Name=default-release
+IsRelative=1
+Path=abcd.default-release
+Default=1
+
+[General]
+StartWithLastProfile=1
+";
+
+    fn write_profiles(profiles_dir: &Path) {
+        std::fs::create_dir_all(profiles_dir.join("efgh.work")).unwrap();
+        std::fs::write(profiles_dir.join("profiles.ini"), PROFILES_INI).unwrap();
+        std::fs::write(
+            profiles_dir.join("efgh.work/signedInUser.json"),
+            r#"{"version":1,"accountData":{"email":"work@example.com"}}"#,
+        )
+        .unwrap();
+    }
+
+    fn profiles_of(browsers: &[FirefoxBrowser], id: &str) -> Vec<(String, Option<String>)> {
+        let browser = browsers.iter().find(|b| b.id == id).unwrap();
+        browser.profiles().into_iter().map(|p| (p.name, p.email)).collect()
+    }
+
+    #[test]
+    fn macos_layout() {
+        let home = test_home("firefox-macos");
+        write_profiles(&home.join("Library/Application Support/Firefox"));
+
+        let profiles = profiles_of(&macos_browsers(&home), "firefox");
+        assert_eq!(
+            profiles,
+            vec![("work".to_string(), Some("work@example.com".to_string())), ("default-release".to_string(), None)]
+        );
+    }
+
+    #[test]
+    fn linux_layout() {
+        let home = test_home("firefox-linux");
+        write_profiles(&home.join(".mozilla/firefox"));
+        write_profiles(&home.join(".var/app/org.mozilla.firefox/.mozilla/firefox"));
+
+        let browsers = linux_browsers(&home);
+        assert_eq!(profiles_of(&browsers, "firefox").len(), 2);
+        assert_eq!(profiles_of(&browsers, "firefox-flatpak").len(), 2);
+        assert!(profiles_of(&browsers, "firefox-snap").is_empty());
+
+        let work = &browsers[0].profiles()[0];
+        assert_eq!(work.id, home.join(".mozilla/firefox/efgh.work").to_string_lossy());
+    }
+
+    #[test]
+    fn only_listed_profiles_are_opened() {
+        let home = test_home("firefox-resolve");
+        write_profiles(&home.join(".mozilla/firefox"));
+        let firefox = &linux_browsers(&home)[0];
+
+        let work = home.join(".mozilla/firefox/efgh.work").to_string_lossy().to_string();
+        assert_eq!(firefox.resolve_profile(&work), Ok(work.clone()));
+        for profile in ["/tmp/elsewhere", "-P", "efgh.work", ""] {
+            assert!(firefox.resolve_profile(profile).is_err(), "{}", profile);
+        }
+    }
+}
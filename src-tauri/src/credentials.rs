@@ -0,0 +1,601 @@
+// Where an account's OAuth tokens come from, and refreshing them.
+//
+// All sources hold Claude Code's credentials JSON ({"claudeAiOauth": {...}}).
+// Claude Code keeps it in the keychain on macOS and in
+// ~/.claude/.credentials.json elsewhere; other sources are for additional
+// accounts, e.g. a second Claude Code install with its own CLAUDE_CONFIG_DIR,
+// or the app's own login (see oauth.rs) for machines without Claude Code.
+//
+// Reading the keychain means running `security`, so tokens are cached per
+// source: polls use the cache, and it is only read again after the app's own
+// writes or when credentials_watcher.rs sees the source change.
+
+use crate::secret::SecretString;
+use crate::settings::ApiSettings;
+use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use zeroize::Zeroizing;
+
+const CLAUDE_CODE_KEYCHAIN_SERVICE: &str = "Claude Code-credentials";
+const APP_KEYCHAIN_SERVICE: &str = "claude-usage-monitor-credentials";
+
+// `security` exit status when the keychain item doesn't exist
+const KEYCHAIN_ITEM_NOT_FOUND: i32 = 44;
+
+pub const NOT_LOGGED_IN: &str = "Not logged in to Claude";
+
+// The token each source held when last read or written; None inside means
+// logged out. Few enough sources that a Vec will do
+static TOKEN_CACHE: Mutex<Vec<(CredentialSource, Option<OAuthToken>)>> = Mutex::new(Vec::new());
+
+fn cached_token(source: &CredentialSource) -> Option<Option<OAuthToken>> {
+    let cache = TOKEN_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.iter().find(|(s, _)| s == source).map(|(_, token)| token.clone())
+}
+
+fn cache_token(source: &CredentialSource, token: Option<OAuthToken>) {
+    let mut cache = TOKEN_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.retain(|(s, _)| s != source);
+    cache.push((source.clone(), token));
+}
+
+// Fingerprint of what the app itself last wrote to each source, so the
+// credential watcher can tell the app's own token refreshes from logins
+static OWN_WRITES: Mutex<Vec<(CredentialSource, Option<u64>)>> = Mutex::new(Vec::new());
+
+// Runs a blocking read or write of the source on the blocking thread pool,
+// so a slow `security` never holds up the async runtime
+async fn unblock<T: Send + 'static>(
+    source: &CredentialSource,
+    f: impl FnOnce(&CredentialSource) -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    let source = source.clone();
+    tokio::task::spawn_blocking(move || f(&source))
+        .await
+        .map_err(|e| format!("Credentials task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum CredentialSource {
+    #[default]
+    ClaudeCode,
+    // Signed in from the app itself; keychain on macOS, else credentials.json
+    // next to settings.toml
+    App,
+    // macOS keychain generic password, by service name
+    Keychain { service: String },
+    // A leading "~/" is expanded to the home directory
+    File { path: String },
+}
+
+// Only ever deserialized: writing it back goes through StoredCredentials, so
+// tokens can't end up in an event or command result by accident
+#[derive(Debug, Deserialize, Clone)]
+pub struct OAuthToken {
+    #[serde(rename = "accessToken")]
+    pub access_token: SecretString,
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: Option<SecretString>,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: Option<i64>,
+    #[serde(rename = "scopes")]
+    pub scopes: Option<Vec<String>>,
+    #[serde(rename = "subscriptionType")]
+    pub subscription_type: Option<String>,
+    #[serde(rename = "rateLimitTier")]
+    pub rate_limit_tier: Option<String>,
+}
+
+// No Debug: other entries may hold secrets of their own
+#[derive(Deserialize)]
+struct ClaudeCredentials {
+    #[serde(rename = "claudeAiOauth")]
+    claude_ai_oauth: Option<OAuthToken>,
+    // Kept as-is when the token is rewritten after a refresh
+    #[serde(flatten)]
+    other: serde_json::Map<String, serde_json::Value>,
+}
+
+// The credentials JSON as written back to the source, built only for that
+#[derive(Serialize)]
+struct StoredCredentials<'a> {
+    #[serde(rename = "claudeAiOauth")]
+    claude_ai_oauth: StoredToken<'a>,
+    #[serde(flatten)]
+    other: &'a serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct StoredToken<'a> {
+    #[serde(rename = "accessToken")]
+    access_token: &'a str,
+    #[serde(rename = "refreshToken")]
+    refresh_token: Option<&'a str>,
+    #[serde(rename = "expiresAt")]
+    expires_at: Option<i64>,
+    #[serde(rename = "scopes")]
+    scopes: &'a Option<Vec<String>>,
+    #[serde(rename = "subscriptionType")]
+    subscription_type: &'a Option<String>,
+    #[serde(rename = "rateLimitTier")]
+    rate_limit_tier: &'a Option<String>,
+}
+
+impl<'a> From<&'a OAuthToken> for StoredToken<'a> {
+    fn from(token: &'a OAuthToken) -> Self {
+        Self {
+            access_token: token.access_token.expose(),
+            refresh_token: token.refresh_token.as_ref().map(SecretString::expose),
+            expires_at: token.expires_at,
+            scopes: &token.scopes,
+            subscription_type: &token.subscription_type,
+            rate_limit_tier: &token.rate_limit_tier,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenRefreshResponse {
+    access_token: SecretString,
+    refresh_token: SecretString,
+    expires_in: i64,
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+fn app_file() -> PathBuf {
+    crate::settings::settings_path().with_file_name("credentials.json")
+}
+
+fn claude_code_file() -> PathBuf {
+    std::env::var_os("CLAUDE_CONFIG_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".claude"))
+        .join(".credentials.json")
+}
+
+impl CredentialSource {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            CredentialSource::Keychain { .. } if !cfg!(target_os = "macos") => {
+                Err("keychain credentials are only supported on macOS".to_string())
+            }
+            CredentialSource::Keychain { service } if service.is_empty() => {
+                Err("keychain service must not be empty".to_string())
+            }
+            CredentialSource::File { path } if path.is_empty() => Err("credentials path must not be empty".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    fn keychain_service(&self) -> Option<&str> {
+        match self {
+            CredentialSource::ClaudeCode if cfg!(target_os = "macos") => Some(CLAUDE_CODE_KEYCHAIN_SERVICE),
+            CredentialSource::App if cfg!(target_os = "macos") => Some(APP_KEYCHAIN_SERVICE),
+            CredentialSource::Keychain { service } => Some(service),
+            _ => None,
+        }
+    }
+
+    // The file to watch for changes; keychain sources are polled instead
+    pub fn file_path(&self) -> Option<PathBuf> {
+        match self {
+            CredentialSource::ClaudeCode if !cfg!(target_os = "macos") => Some(claude_code_file()),
+            CredentialSource::App if !cfg!(target_os = "macos") => Some(app_file()),
+            CredentialSource::File { path } => Some(expand_home(path)),
+            _ => None,
+        }
+    }
+
+    // None when there are no credentials at all, i.e. after a logout
+    fn read_raw(&self) -> Result<Option<String>, String> {
+        if let Some(service) = self.keychain_service() {
+            return read_keychain(service);
+        }
+        let path = self.file_path().unwrap_or_default();
+        match std::fs::read_to_string(&path) {
+            Ok(s) => Ok(Some(s.trim().to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Failed to read credentials {}: {}", path.display(), e)),
+        }
+    }
+
+    // Changes whenever the stored credentials do, without keeping them around.
+    // Keychain items go by their modification date, which `security` reports
+    // without reading the secret
+    fn fingerprint(&self) -> Result<Option<u64>, String> {
+        let current = match self.keychain_service() {
+            Some(service) => keychain_modified(service)?.map(|at| at.to_rfc3339()),
+            None => self.read_raw()?,
+        };
+        Ok(current.map(|current| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            current.hash(&mut hasher);
+            hasher.finish()
+        }))
+    }
+
+    // Whether the credentials are as the app itself last wrote them
+    pub fn is_own_write(&self, fingerprint: Option<u64>) -> bool {
+        let own_writes = OWN_WRITES.lock().unwrap_or_else(|e| e.into_inner());
+        own_writes.iter().any(|(s, written)| s == self && *written == fingerprint)
+    }
+
+    fn write_raw(&self, json: &str) -> Result<(), String> {
+        if let Some(service) = self.keychain_service() {
+            return write_keychain(service, json);
+        }
+        let path = self.file_path().unwrap_or_default();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let write_error = |e: std::io::Error| format!("Failed to write credentials {}: {}", path.display(), e);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // Tokens are as good as a password: never readable by others, not even
+        // for a moment, and an existing file is tightened before it's written
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&path).map_err(write_error)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600)).map_err(write_error)?;
+        }
+        file.write_all(json.as_bytes()).map_err(write_error)
+    }
+
+    // Removes the credentials entirely, i.e. logs out
+    pub fn delete(&self) -> Result<(), String> {
+        if let Some(service) = self.keychain_service() {
+            delete_keychain(service)?;
+        } else {
+            let path = self.file_path().unwrap_or_default();
+            match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(format!("Failed to remove credentials {}: {}", path.display(), e));
+                }
+                _ => {}
+            }
+        }
+        cache_token(self, None);
+        Ok(())
+    }
+
+    // Where the credentials are read from, for diagnostics
+    pub fn describe(&self) -> String {
+        let kind = match self {
+            CredentialSource::ClaudeCode => "Claude Code",
+            CredentialSource::App => "app sign-in",
+            CredentialSource::Keychain { .. } | CredentialSource::File { .. } => "configured",
+        };
+        match (self.keychain_service(), self.file_path()) {
+            (Some(service), _) => format!("{}, keychain item \"{}\"", kind, service),
+            (None, Some(path)) => format!("{}, file {}", kind, path.display()),
+            (None, None) => kind.to_string(),
+        }
+    }
+
+    // When the credentials were last written, i.e. at login or the last
+    // token refresh
+    pub fn modified(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        if let Some(service) = self.keychain_service() {
+            return keychain_modified(service).ok().flatten();
+        }
+        let modified = std::fs::metadata(self.file_path()?).ok()?.modified().ok()?;
+        Some(modified.into())
+    }
+
+    // None when logged out: no credentials, or no OAuth token in them.
+    // Always reads the source, blocking; the result replaces the cached one
+    pub fn read_token_opt(&self) -> Result<Option<OAuthToken>, String> {
+        let token = match self.read_raw()? {
+            Some(json_str) => {
+                let creds: ClaudeCredentials =
+                    serde_json::from_str(&json_str).map_err(|e| format!("Failed to parse credentials: {}", e))?;
+                creds.claude_ai_oauth
+            }
+            None => None,
+        };
+        cache_token(self, token.clone());
+        Ok(token)
+    }
+
+    pub fn read_token(&self) -> Result<OAuthToken, String> {
+        self.read_token_opt()?.ok_or_else(|| NOT_LOGGED_IN.to_string())
+    }
+
+    pub fn store_token(&self, new_token: &OAuthToken) -> Result<(), String> {
+        // Read existing credentials to preserve other fields
+        let other = match self.read_raw()? {
+            Some(json_str) => {
+                let creds: ClaudeCredentials =
+                    serde_json::from_str(&json_str).map_err(|e| format!("Failed to parse credentials: {}", e))?;
+                creds.other
+            }
+            None => serde_json::Map::new(),
+        };
+
+        let creds = StoredCredentials { claude_ai_oauth: new_token.into(), other: &other };
+        let new_json =
+            serde_json::to_string(&creds).map_err(|e| format!("Failed to serialize credentials: {}", e))?;
+        self.write_raw(&new_json)?;
+        cache_token(self, Some(new_token.clone()));
+        if let Ok(fingerprint) = self.fingerprint() {
+            let mut own_writes = OWN_WRITES.lock().unwrap_or_else(|e| e.into_inner());
+            own_writes.retain(|(s, _)| s != self);
+            own_writes.push((self.clone(), fingerprint));
+        }
+        Ok(())
+    }
+
+    // Drops the cached token, so the next load reads the source again
+    pub fn invalidate(&self) {
+        TOKEN_CACHE.lock().unwrap_or_else(|e| e.into_inner()).retain(|(s, _)| s != self);
+    }
+
+    // Like read_token_opt, but from the cache when possible and otherwise
+    // off the async runtime
+    pub async fn load_token_opt(&self) -> Result<Option<OAuthToken>, String> {
+        match cached_token(self) {
+            Some(token) => Ok(token),
+            None => unblock(self, CredentialSource::read_token_opt).await,
+        }
+    }
+
+    pub async fn load_token(&self) -> Result<OAuthToken, String> {
+        self.load_token_opt().await?.ok_or_else(|| NOT_LOGGED_IN.to_string())
+    }
+
+    pub async fn save_token(&self, token: &OAuthToken) -> Result<(), String> {
+        let token = token.clone();
+        unblock(self, move |source| source.store_token(&token)).await
+    }
+
+    pub async fn remove(&self) -> Result<(), String> {
+        unblock(self, CredentialSource::delete).await
+    }
+
+    pub async fn load_fingerprint(&self) -> Result<Option<u64>, String> {
+        unblock(self, CredentialSource::fingerprint).await
+    }
+}
+
+fn read_keychain(service: &str) -> Result<Option<String>, String> {
+    let output = Command::new("security")
+        .args(["find-generic-password", "-s", service, "-w"])
+        .output()
+        .map_err(|e| format!("Failed to run security command: {}", e))?;
+
+    if output.status.code() == Some(KEYCHAIN_ITEM_NOT_FOUND) {
+        return Ok(None);
+    }
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Keychain access failed: {}", stderr));
+    }
+
+    String::from_utf8(output.stdout)
+        .map_err(|e| format!("Invalid UTF-8 in credentials: {}", e))
+        .map(|s| Some(s.trim().to_string()))
+}
+
+// From the item's attributes, which include a line like
+//     "mdat"<timedate>=0x3230...  "20250101120000Z\000"
+// None when the item doesn't exist
+fn keychain_modified(service: &str) -> Result<Option<chrono::DateTime<chrono::Utc>>, String> {
+    let output = Command::new("security")
+        .args(["find-generic-password", "-s", service])
+        .output()
+        .map_err(|e| format!("Failed to run security command: {}", e))?;
+    if output.status.code() == Some(KEYCHAIN_ITEM_NOT_FOUND) {
+        return Ok(None);
+    }
+    let attributes = String::from_utf8_lossy(&output.stdout);
+    attributes
+        .lines()
+        .find(|line| line.trim_start().starts_with("\"mdat\""))
+        .and_then(|line| line.split_once("  \""))
+        .and_then(|(_, quoted)| chrono::NaiveDateTime::parse_from_str(quoted.get(..14)?, "%Y%m%d%H%M%S").ok())
+        .map(|time| Some(time.and_utc()))
+        .ok_or_else(|| format!("No modification date for keychain item \"{}\"", service))
+}
+
+fn delete_keychain(service: &str) -> Result<(), String> {
+    let output = Command::new("security")
+        .args(["delete-generic-password", "-s", service])
+        .output()
+        .map_err(|e| format!("Failed to run security command: {}", e))?;
+
+    if !output.status.success() && output.status.code() != Some(KEYCHAIN_ITEM_NOT_FOUND) {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Keychain delete failed: {}", stderr));
+    }
+    Ok(())
+}
+
+fn write_keychain(service: &str, json: &str) -> Result<(), String> {
+    // Delete existing keychain entry
+    let _ = delete_keychain(service);
+
+    // Add updated credentials. The command goes to `security -i` on stdin,
+    // with the secret hex-encoded for -X, so it never shows up in the process
+    // list the way `-w <secret>` would
+    let hex = Zeroizing::new(json.bytes().map(|b| format!("{:02x}", b)).collect::<String>());
+    let service = service.replace('\\', "\\\\").replace('"', "\\\"");
+    let command = Zeroizing::new(format!("add-generic-password -U -s \"{}\" -a \"\" -X {}\n", service, *hex));
+    let mut child = Command::new("security")
+        .arg("-i")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to update keychain: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(command.as_bytes())
+            .map_err(|e| format!("Failed to update keychain: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to update keychain: {}", e))?;
+
+    // Interactive mode reports a failed command on stderr, not always in
+    // the exit status
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() || !stderr.trim().is_empty() {
+        return Err(format!("Keychain update failed: {}", stderr));
+    }
+
+    Ok(())
+}
+
+// Exchanges the source's refresh token for a new access token and writes the
+// result back to the source, so Claude Code sees the refreshed token too
+pub async fn refresh_oauth_token(api: &ApiSettings, source: &CredentialSource) -> Result<OAuthToken, String> {
+    // Not from the cache: refresh tokens are single use, and Claude Code may
+    // have used this one since it was cached
+    let current = unblock(source, CredentialSource::read_token).await?;
+    let refresh_token = current
+        .refresh_token
+        .clone()
+        .ok_or_else(|| "No refresh token found".to_string())?;
+
+    let client = api.client()?;
+
+    let params = [
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token.expose()),
+        ("client_id", crate::oauth::CLIENT_ID),
+    ];
+
+    let response = client
+        .post(crate::oauth::TOKEN_URL)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .form(&params)
+        .send()
+        .await
+        .map_err(|e| format!("Token refresh request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("Token refresh failed ({}): {}", status, body));
+    }
+
+    let token_response: TokenRefreshResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse token response: {}", e))?;
+
+    let new_expires_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64 + (token_response.expires_in * 1000))
+        .ok();
+
+    // Subscription info isn't part of the refresh response, so carry it over
+    let new_token = OAuthToken {
+        access_token: token_response.access_token,
+        refresh_token: Some(token_response.refresh_token),
+        expires_at: new_expires_at,
+        scopes: current.scopes,
+        subscription_type: current.subscription_type,
+        rate_limit_tier: current.rate_limit_tier,
+    };
+
+    source.save_token(&new_token).await?;
+
+    Ok(new_token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_source(name: &str) -> CredentialSource {
+        let dir = std::env::temp_dir().join(format!("claude-usage-monitor-credentials-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        CredentialSource::File { path: dir.join("credentials.json").to_string_lossy().to_string() }
+    }
+
+    fn write(source: &CredentialSource, access_token: &str) {
+        let json = format!(r#"{{"claudeAiOauth": {{"accessToken": "{}"}}}}"#, access_token);
+        std::fs::write(source.file_path().unwrap(), json).unwrap();
+    }
+
+    async fn loaded(source: &CredentialSource) -> Option<String> {
+        let token = source.load_token_opt().await.unwrap();
+        token.map(|token| token.access_token.expose().to_string())
+    }
+
+    #[tokio::test]
+    async fn cached_until_invalidated() {
+        let source = file_source("cache");
+        write(&source, "access-1");
+        assert_eq!(loaded(&source).await.as_deref(), Some("access-1"));
+
+        // Changed behind the app's back: not seen until invalidated
+        write(&source, "access-2");
+        assert_eq!(loaded(&source).await.as_deref(), Some("access-1"));
+        source.invalidate();
+        assert_eq!(loaded(&source).await.as_deref(), Some("access-2"));
+
+        // The app's own writes and deletes update the cache directly
+        let mut token = source.load_token().await.unwrap();
+        token.access_token = SecretString::new("access-3".to_string());
+        source.save_token(&token).await.unwrap();
+        write(&source, "access-4");
+        assert_eq!(loaded(&source).await.as_deref(), Some("access-3"));
+        source.remove().await.unwrap();
+        write(&source, "access-5");
+        assert_eq!(loaded(&source).await, None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn written_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let source = file_source("private");
+        let path = source.file_path().unwrap();
+        write(&source, "access-1");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let token = source.load_token().await.unwrap();
+        source.save_token(&token).await.unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        source.remove().await.unwrap();
+        source.save_token(&token).await.unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[tokio::test]
+    async fn own_writes_are_recognised() {
+        let source = file_source("own-writes");
+        write(&source, "access-1");
+        let external = source.load_fingerprint().await.unwrap();
+        assert!(!source.is_own_write(external));
+
+        let mut token = source.load_token().await.unwrap();
+        token.access_token = SecretString::new("access-2".to_string());
+        source.save_token(&token).await.unwrap();
+        assert!(source.is_own_write(source.load_fingerprint().await.unwrap()));
+
+        write(&source, "access-3");
+        assert!(!source.is_own_write(source.load_fingerprint().await.unwrap()));
+    }
+}
@@ -0,0 +1,100 @@
+// Notices `claude /login` and `/logout`, or any other change to an account's
+// credentials, when it happens rather than at the next poll. Credential files
+// are watched; keychain items can't be, so their modification date is polled,
+// as are files whose directory doesn't exist yet. A change invalidates the
+// source's cached token, unless it was the app's own token refresh.
+
+use crate::credentials::CredentialSource;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Instant};
+
+// Claude Code writes the file in several steps; wait for it to settle
+const DEBOUNCE: Duration = Duration::from_millis(300);
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+// Each check runs `security`, so keychain items are checked less often; a
+// login missed meanwhile still shows up as a 401 on the next fetch
+const KEYCHAIN_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct CredentialWatcher {
+    source: CredentialSource,
+    fingerprint: Option<u64>,
+    events: mpsc::UnboundedReceiver<()>,
+    // Stops watching when dropped; None when polling
+    watcher: Option<RecommendedWatcher>,
+    // When the credentials are next compared: after the debounce once the
+    // file changed, or the next poll. Kept here so it survives changed()
+    // being cancelled
+    check_at: Option<Instant>,
+}
+
+fn watch_file(source: &CredentialSource, tx: mpsc::UnboundedSender<()>) -> Option<RecommendedWatcher> {
+    let path = source.file_path()?;
+    // Watch the directory so that files replaced by a rename are seen too
+    let dir = path.parent()?.to_path_buf();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if event.is_ok_and(|event| event.paths.iter().any(|p| p == &path)) {
+            let _ = tx.send(());
+        }
+    })
+    .ok()?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive).ok()?;
+    Some(watcher)
+}
+
+impl CredentialWatcher {
+    pub async fn new(source: &CredentialSource) -> Self {
+        let (tx, events) = mpsc::unbounded_channel();
+        Self {
+            source: source.clone(),
+            fingerprint: source.load_fingerprint().await.ok().flatten(),
+            events,
+            watcher: watch_file(source, tx),
+            check_at: None,
+        }
+    }
+
+    fn poll_interval(&self) -> Duration {
+        if self.source.file_path().is_some() {
+            POLL_INTERVAL
+        } else {
+            KEYCHAIN_POLL_INTERVAL
+        }
+    }
+
+    // Resolves once the credentials differ from when this was last called.
+    // Cancel safe, so it can be used in select!
+    pub async fn changed(&mut self) {
+        loop {
+            let Some(check_at) = self.check_at else {
+                if self.watcher.is_none() {
+                    self.check_at = Some(Instant::now() + self.poll_interval());
+                } else if self.events.recv().await.is_some() {
+                    self.check_at = Some(Instant::now() + DEBOUNCE);
+                } else {
+                    // Watcher gone; fall back to polling
+                    self.watcher = None;
+                }
+                continue;
+            };
+            sleep_until(check_at).await;
+            while self.events.try_recv().is_ok() {}
+
+            // Unreadable for a moment, e.g. mid-write: wait for the next change
+            let fingerprint = self.source.load_fingerprint().await;
+            self.check_at = None;
+            let Ok(fingerprint) = fingerprint else {
+                continue;
+            };
+            if fingerprint != self.fingerprint {
+                self.fingerprint = fingerprint;
+                // The app's own write already updated the cached token
+                if !self.source.is_own_write(fingerprint) {
+                    self.source.invalidate();
+                    return;
+                }
+            }
+        }
+    }
+}
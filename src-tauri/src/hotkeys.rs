@@ -0,0 +1,143 @@
+// Global keyboard shortcuts, configured as accelerator strings such as
+// "CmdOrCtrl+Shift+U".
+
+use crate::AppState;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct HotkeySettings {
+    pub toggle_popup: Option<String>,
+    pub refresh: Option<String>,
+    pub copy_summary: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HotkeyAction {
+    TogglePopup,
+    Refresh,
+    CopySummary,
+}
+
+impl HotkeyAction {
+    fn label(self) -> &'static str {
+        match self {
+            HotkeyAction::TogglePopup => "toggle popup",
+            HotkeyAction::Refresh => "refresh now",
+            HotkeyAction::CopySummary => "copy usage summary",
+        }
+    }
+}
+
+// Parses every configured shortcut and rejects the same key combination
+// being bound to two actions
+fn parse_hotkeys(settings: &HotkeySettings) -> Result<Vec<(HotkeyAction, Shortcut)>, String> {
+    let configured = [
+        (HotkeyAction::TogglePopup, &settings.toggle_popup),
+        (HotkeyAction::Refresh, &settings.refresh),
+        (HotkeyAction::CopySummary, &settings.copy_summary),
+    ];
+
+    let mut parsed: Vec<(HotkeyAction, Shortcut)> = Vec::new();
+    for (action, accelerator) in configured {
+        let accelerator = match accelerator.as_deref().map(str::trim) {
+            Some(a) if !a.is_empty() => a,
+            _ => continue,
+        };
+        let shortcut = Shortcut::from_str(accelerator)
+            .map_err(|e| format!("Invalid shortcut '{}' for {}: {}", accelerator, action.label(), e))?;
+        if let Some((other, _)) = parsed.iter().find(|(_, s)| *s == shortcut) {
+            return Err(format!(
+                "Shortcut '{}' is assigned to both {} and {}",
+                accelerator,
+                other.label(),
+                action.label()
+            ));
+        }
+        parsed.push((action, shortcut));
+    }
+    Ok(parsed)
+}
+
+pub fn validate_hotkeys(settings: &HotkeySettings) -> Result<(), String> {
+    parse_hotkeys(settings).map(|_| ())
+}
+
+fn run_action(app: &AppHandle, action: HotkeyAction) {
+    match action {
+        HotkeyAction::TogglePopup => crate::toggle_window_at_tray(app),
+        HotkeyAction::Refresh => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let _ = crate::refresh_usage(None, app.state::<AppState>(), app.clone()).await;
+            });
+        }
+        HotkeyAction::CopySummary => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let _ = crate::tray_menu::copy_usage(&app).await;
+            });
+        }
+    }
+}
+
+// Replaces all registered shortcuts with the ones in `settings`. Fails if a
+// shortcut is invalid or already taken by another application.
+pub fn register_hotkeys(app: &AppHandle, settings: &HotkeySettings) -> Result<(), String> {
+    let hotkeys = parse_hotkeys(settings)?;
+    let global_shortcut = app.global_shortcut();
+    global_shortcut
+        .unregister_all()
+        .map_err(|e| format!("Failed to unregister shortcuts: {}", e))?;
+
+    for (action, shortcut) in hotkeys {
+        global_shortcut
+            .on_shortcut(shortcut, move |app, _shortcut, event| {
+                if event.state() == ShortcutState::Pressed {
+                    run_action(app, action);
+                }
+            })
+            .map_err(|e| format!("Shortcut '{}' for {} is unavailable: {}", shortcut, action.label(), e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(toggle_popup: &str, refresh: &str) -> HotkeySettings {
+        HotkeySettings {
+            toggle_popup: Some(toggle_popup.to_string()),
+            refresh: Some(refresh.to_string()),
+            copy_summary: None,
+        }
+    }
+
+    #[test]
+    fn parses_configured_shortcuts() {
+        let parsed = parse_hotkeys(&settings("CmdOrCtrl+Shift+U", " Alt+R ")).unwrap();
+        let actions: Vec<_> = parsed.iter().map(|(action, _)| *action).collect();
+        assert_eq!(actions, [HotkeyAction::TogglePopup, HotkeyAction::Refresh]);
+
+        // Blank ones are skipped
+        assert_eq!(parse_hotkeys(&settings("CmdOrCtrl+Shift+U", "  ")).unwrap().len(), 1);
+        assert!(parse_hotkeys(&HotkeySettings::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_shortcuts() {
+        let e = validate_hotkeys(&settings("CmdOrCtrl+Shift+U", "Shift+NotAKey")).unwrap_err();
+        assert!(e.starts_with("Invalid shortcut 'Shift+NotAKey' for refresh now"), "{}", e);
+        assert!(validate_hotkeys(&settings("CmdOrCtrl+", "Alt+R")).is_err());
+    }
+
+    #[test]
+    fn rejects_a_shortcut_bound_twice() {
+        let e = validate_hotkeys(&settings("CmdOrCtrl+Shift+U", "Shift+CmdOrCtrl+U")).unwrap_err();
+        assert_eq!(e, "Shortcut 'Shift+CmdOrCtrl+U' is assigned to both toggle popup and refresh now");
+    }
+}
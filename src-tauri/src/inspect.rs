@@ -0,0 +1,213 @@
+// What the app knows about each account's tokens, for working out why auth
+// broke. Shared by the inspect_tokens command and the `inspect-token` CLI
+// subcommand. Tokens themselves never appear, only a short prefix.
+
+use crate::accounts::AccountSettings;
+use crate::credentials::OAuthToken;
+use crate::subscription::SubscriptionTier;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fmt;
+
+// Scopes the app's endpoints need, with what breaks without them
+const REQUIRED_SCOPES: [(&str, &str); 1] = [("user:profile", "the usage and profile endpoints")];
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TokenReport {
+    pub account_id: String,
+    pub account_name: String,
+    pub source: String,
+    pub logged_in: bool,
+    // e.g. "sk-ant-o… (108 chars)"
+    pub access_token: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub expired: Option<bool>,
+    // When the credentials were last written: login or last token refresh
+    pub last_refreshed: Option<DateTime<Utc>>,
+    pub scopes: Option<Vec<String>>,
+    pub subscription_type: Option<String>,
+    pub rate_limit_tier: Option<String>,
+    pub subscription: Option<SubscriptionTier>,
+    pub has_refresh_token: bool,
+    pub warnings: Vec<String>,
+    // Set when the credentials couldn't be read at all
+    pub error: Option<String>,
+}
+
+// Up to 8 leading characters, and never more than a quarter of the secret,
+// so short ones aren't shown whole
+fn redact(secret: &str) -> String {
+    let len = secret.chars().count();
+    let prefix: String = secret.chars().take(8.min(len / 4)).collect();
+    format!("{}… ({} chars)", prefix, len)
+}
+
+fn warnings(token: &OAuthToken, expired: Option<bool>) -> Vec<String> {
+    let mut warnings = Vec::new();
+    match token.scopes {
+        Some(ref scopes) => {
+            for (scope, needed_by) in REQUIRED_SCOPES {
+                if !scopes.iter().any(|s| s == scope) {
+                    warnings.push(format!("missing scope '{}', needed by {}", scope, needed_by));
+                }
+            }
+        }
+        None => warnings.push("no scopes recorded; can't tell whether the token may read usage".to_string()),
+    }
+    match (expired, token.refresh_token.is_some()) {
+        (Some(true), false) => warnings.push("token has expired and there is no refresh token; log in again".to_string()),
+        (_, false) => warnings.push("no refresh token; the token can't be renewed when it expires".to_string()),
+        _ => {}
+    }
+    warnings
+}
+
+pub fn inspect(account: &AccountSettings) -> TokenReport {
+    let mut report = TokenReport {
+        account_id: account.id.clone(),
+        account_name: account.name.clone(),
+        source: account.credentials.describe(),
+        logged_in: false,
+        access_token: None,
+        expires_at: None,
+        expired: None,
+        last_refreshed: None,
+        scopes: None,
+        subscription_type: None,
+        rate_limit_tier: None,
+        subscription: None,
+        has_refresh_token: false,
+        warnings: Vec::new(),
+        error: None,
+    };
+
+    let token = match account.credentials.read_token_opt() {
+        Ok(Some(token)) => token,
+        Ok(None) => return report,
+        Err(e) => {
+            report.error = Some(e);
+            return report;
+        }
+    };
+
+    let expires_at = token.expires_at.and_then(DateTime::from_timestamp_millis);
+    let expired = expires_at.map(|at| at <= Utc::now());
+    TokenReport {
+        logged_in: true,
+        access_token: Some(redact(token.access_token.expose())),
+        expires_at,
+        expired,
+        last_refreshed: account.credentials.modified(),
+        scopes: token.scopes.clone(),
+        subscription_type: token.subscription_type.clone(),
+        rate_limit_tier: token.rate_limit_tier.clone(),
+        subscription: Some(SubscriptionTier::parse(
+            token.subscription_type.as_deref(),
+            token.rate_limit_tier.as_deref(),
+        )),
+        has_refresh_token: token.refresh_token.is_some(),
+        warnings: warnings(&token, expired),
+        ..report
+    }
+}
+
+// "3h 12m" or "45s"
+fn format_duration(duration: chrono::Duration) -> String {
+    let secs = duration.num_seconds().abs();
+    match (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60) {
+        (0, 0, 0) => format!("{}s", secs),
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
+
+fn or_unknown(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("unknown")
+}
+
+impl fmt::Display for TokenReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let now = Utc::now();
+        writeln!(f, "{} ({})", self.account_name, self.account_id)?;
+        writeln!(f, "  source:            {}", self.source)?;
+        if let Some(ref error) = self.error {
+            return writeln!(f, "  error:             {}", error);
+        }
+        if !self.logged_in {
+            return writeln!(f, "  status:            not logged in");
+        }
+        writeln!(f, "  access token:      {}", or_unknown(&self.access_token))?;
+        match self.expires_at {
+            Some(at) if at <= now => writeln!(f, "  expires:           {} (expired {} ago)", at, format_duration(now - at))?,
+            Some(at) => writeln!(f, "  expires:           {} (in {})", at, format_duration(at - now))?,
+            None => writeln!(f, "  expires:           unknown")?,
+        }
+        match self.last_refreshed {
+            Some(at) => writeln!(f, "  last refreshed:    {} ({} ago)", at, format_duration(now - at))?,
+            None => writeln!(f, "  last refreshed:    unknown")?,
+        }
+        let scopes = self.scopes.as_ref().map(|scopes| scopes.join(" "));
+        writeln!(f, "  scopes:            {}", or_unknown(&scopes))?;
+        writeln!(f, "  subscription_type: {}", or_unknown(&self.subscription_type))?;
+        writeln!(f, "  rate_limit_tier:   {}", or_unknown(&self.rate_limit_tier))?;
+        writeln!(f, "  refresh token:     {}", if self.has_refresh_token { "present" } else { "missing" })?;
+        for warning in &self.warnings {
+            writeln!(f, "  warning:           {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(scopes: Option<&[&str]>, refresh_token: bool) -> OAuthToken {
+        OAuthToken {
+            access_token: "sk-ant-oat01-secret".to_string().into(),
+            refresh_token: refresh_token.then(|| "sk-ant-ort01-secret".to_string().into()),
+            expires_at: None,
+            scopes: scopes.map(|scopes| scopes.iter().map(|s| s.to_string()).collect()),
+            subscription_type: None,
+            rate_limit_tier: None,
+        }
+    }
+
+    #[test]
+    fn warns_about_missing_scopes_and_refresh_tokens() {
+        assert!(warnings(&token(Some(&["user:inference", "user:profile"]), true), Some(false)).is_empty());
+
+        let missing = warnings(&token(Some(&["user:inference"]), true), Some(false));
+        assert_eq!(missing, ["missing scope 'user:profile', needed by the usage and profile endpoints"]);
+        assert_eq!(warnings(&token(None, true), None).len(), 1);
+
+        let expired = warnings(&token(Some(&["user:profile"]), false), Some(true));
+        assert_eq!(expired, ["token has expired and there is no refresh token; log in again"]);
+        let unrenewable = warnings(&token(Some(&["user:profile"]), false), Some(false));
+        assert_eq!(unrenewable, ["no refresh token; the token can't be renewed when it expires"]);
+    }
+
+    #[test]
+    fn redacts_all_but_a_prefix() {
+        assert_eq!(redact(&format!("sk-ant-oat01-{}", "x".repeat(95))), "sk-ant-o… (108 chars)");
+        assert_eq!(redact("sk-ant-oat01-secret"), "sk-a… (19 chars)");
+        // Short ones show little or nothing
+        assert_eq!(redact("short"), "s… (5 chars)");
+        assert_eq!(redact("abc"), "… (3 chars)");
+        assert_eq!(redact(""), "… (0 chars)");
+        // Counted in characters, not bytes
+        assert_eq!(redact("ääääääääää"), "ää… (10 chars)");
+    }
+
+    #[test]
+    fn formats_durations() {
+        let secs = chrono::Duration::seconds;
+        assert_eq!(format_duration(secs(45)), "45s");
+        assert_eq!(format_duration(secs(5 * 60 + 30)), "5m");
+        assert_eq!(format_duration(secs(3 * 3600 + 12 * 60)), "3h 12m");
+        assert_eq!(format_duration(secs(2 * 86400 + 5 * 3600)), "2d 5h");
+        // Past times read the same
+        assert_eq!(format_duration(secs(-90)), "1m");
+    }
+}
@@ -1,13 +1,44 @@
+mod accounts;
+mod autostart;
+mod browser;
+mod credentials;
+mod credentials_watcher;
+mod hotkeys;
+mod inspect;
+mod managed;
+mod oauth;
+mod secret;
+mod settings;
+mod settings_watcher;
+mod status;
+mod subscription;
+mod summary;
+mod tray_format;
+mod tray_icon;
+mod tray_menu;
+mod usage_service;
+
+use accounts::{AccountSettings, AccountState, HistoryEntry};
+use autostart::{AutostartMethod, AutostartSettings};
+use browser::BrowserProfile;
+use credentials::CredentialSource;
+use chrono::{DateTime, Utc};
+use hotkeys::HotkeySettings;
+use managed::{EffectiveSettings, ManagedSettings};
 use serde::{Deserialize, Serialize};
+use settings::{ApiSettings, BrowserSettings, Settings, UserSettings};
+use status::{Failure, MonitorStatus};
 use std::sync::Arc;
+use subscription::SubscriptionTier;
 use tauri::{
-    image::Image,
-    menu::{MenuBuilder, MenuItemBuilder},
     tray::{TrayIconBuilder, TrayIconId},
-    AppHandle, Emitter, Manager, PhysicalPosition, ActivationPolicy,
+    AppHandle, Emitter, Manager, PhysicalPosition,
 };
-use tokio::sync::Mutex;
-use tokio::time::{interval, Duration};
+use tauri_plugin_opener::OpenerExt;
+use std::time::Duration;
+use tokio::sync::{Mutex, Notify};
+use tray_format::Template;
+use tray_icon::{IconStyle, TrayIconSettings};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct UsageLimit {
@@ -32,165 +63,12 @@ pub struct UsageResponse {
     pub extra_usage: Option<ExtraUsage>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct ClaudeCredentials {
-    #[serde(rename = "claudeAiOauth")]
-    claude_ai_oauth: Option<OAuthToken>,
-}
-
-// Synchronous version for reading raw credentials JSON
-fn get_raw_credentials_json() -> Result<String, String> {
-    use std::process::Command;
-    let output = Command::new("security")
-        .args(["find-generic-password", "-s", "Claude Code-credentials", "-w"])
-        .output()
-        .map_err(|e| format!("Failed to run security command: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Keychain access failed: {}", stderr));
-    }
-
-    String::from_utf8(output.stdout)
-        .map_err(|e| format!("Invalid UTF-8 in credentials: {}", e))
-        .map(|s| s.trim().to_string())
-}
-
-fn update_keychain_credentials(new_token: &OAuthToken) -> Result<(), String> {
-    use std::process::Command;
-
-    // Read existing credentials to preserve other fields
-    let json_str = get_raw_credentials_json()?;
-    let mut creds: ClaudeCredentialsFull = serde_json::from_str(&json_str)
-        .map_err(|e| format!("Failed to parse credentials: {}", e))?;
-
-    // Update the OAuth token
-    creds.claude_ai_oauth = Some(new_token.clone());
-
-    let new_json = serde_json::to_string(&creds)
-        .map_err(|e| format!("Failed to serialize credentials: {}", e))?;
-
-    // Delete existing keychain entry
-    let _ = Command::new("security")
-        .args(["delete-generic-password", "-s", "Claude Code-credentials"])
-        .output();
-
-    // Add updated credentials
-    let output = Command::new("security")
-        .args([
-            "add-generic-password",
-            "-s", "Claude Code-credentials",
-            "-a", "",
-            "-w", &new_json,
-            "-U",
-        ])
-        .output()
-        .map_err(|e| format!("Failed to update keychain: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Keychain update failed: {}", stderr));
-    }
-
-    Ok(())
-}
-
-async fn refresh_oauth_token(refresh_token: &str) -> Result<OAuthToken, String> {
-    let client = reqwest::Client::new();
-
-    let params = [
-        ("grant_type", "refresh_token"),
-        ("refresh_token", refresh_token),
-    ];
-
-    let response = client
-        .post("https://console.anthropic.com/v1/oauth/token")
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .form(&params)
-        .send()
-        .await
-        .map_err(|e| format!("Token refresh request failed: {}", e))?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        return Err(format!("Token refresh failed ({}): {}", status, body));
-    }
-
-    let token_response: TokenRefreshResponse = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse token response: {}", e))?;
-
-    // Read current credentials to preserve subscription info
-    let json_str = get_raw_credentials_json()?;
-    let current_creds: ClaudeCredentials = serde_json::from_str(&json_str)
-        .map_err(|e| format!("Failed to parse credentials: {}", e))?;
-
-    let current_oauth = current_creds.claude_ai_oauth.unwrap_or(OAuthToken {
-        access_token: String::new(),
-        refresh_token: None,
-        expires_at: None,
-        scopes: None,
-        subscription_type: None,
-        rate_limit_tier: None,
-    });
-
-    let new_expires_at = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map(|d| d.as_millis() as i64 + (token_response.expires_in * 1000))
-        .ok();
-
-    let new_token = OAuthToken {
-        access_token: token_response.access_token,
-        refresh_token: Some(token_response.refresh_token),
-        expires_at: new_expires_at,
-        scopes: current_oauth.scopes,
-        subscription_type: current_oauth.subscription_type,
-        rate_limit_tier: current_oauth.rate_limit_tier,
-    };
-
-    // Update keychain with new credentials
-    update_keychain_credentials(&new_token)?;
-
-    Ok(new_token)
-}
-
-#[derive(Debug, Deserialize, Serialize, Clone)]
-struct OAuthToken {
-    #[serde(rename = "accessToken")]
-    access_token: String,
-    #[serde(rename = "refreshToken")]
-    refresh_token: Option<String>,
-    #[serde(rename = "expiresAt")]
-    expires_at: Option<i64>,
-    #[serde(rename = "scopes")]
-    scopes: Option<Vec<String>>,
-    #[serde(rename = "subscriptionType")]
-    subscription_type: Option<String>,
-    #[serde(rename = "rateLimitTier")]
-    rate_limit_tier: Option<String>,
-}
-
-#[derive(Debug, Deserialize, Serialize, Clone)]
-struct ClaudeCredentialsFull {
-    #[serde(rename = "claudeAiOauth")]
-    claude_ai_oauth: Option<OAuthToken>,
-}
-
-#[derive(Debug, Deserialize)]
-struct TokenRefreshResponse {
-    access_token: String,
-    refresh_token: String,
-    expires_in: i64,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[derive(Debug, Serialize, Clone, Default)]
 pub struct AccountInfo {
     pub email: Option<String>,
     pub display_name: Option<String>,
     pub full_name: Option<String>,
-    pub subscription: Option<String>,
+    pub subscription: Option<SubscriptionTier>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -205,120 +83,129 @@ struct ProfileAccount {
     full_name: Option<String>,
 }
 
-pub struct AppState {
-    pub usage: Arc<Mutex<Option<UsageResponse>>>,
-    pub last_error: Arc<Mutex<Option<String>>>,
-    pub account: Arc<Mutex<Option<AccountInfo>>>,
-}
-
-impl Default for AppState {
-    fn default() -> Self {
-        Self {
-            usage: Arc::new(Mutex::new(None)),
-            last_error: Arc::new(Mutex::new(None)),
-            account: Arc::new(Mutex::new(None)),
-        }
-    }
-}
-
-struct CredentialsInfo {
-    access_token: String,
-    refresh_token: Option<String>,
-    subscription: Option<String>,
+// Payload of `usage-updated`: the usage fields at the top
+// level, as before accounts existed, plus which account they belong to
+#[derive(Debug, Serialize, Clone)]
+pub struct UsageUpdate {
+    pub account_id: String,
+    #[serde(flatten)]
+    pub usage: UsageResponse,
 }
 
-fn format_subscription(subscription_type: Option<&str>, rate_limit_tier: Option<&str>) -> Option<String> {
-    match subscription_type {
-        Some("max") => {
-            let multiplier = rate_limit_tier
-                .and_then(|tier| {
-                    if tier.contains("20x") { Some("20x") }
-                    else if tier.contains("5x") { Some("5x") }
-                    else { None }
-                })
-                .unwrap_or("");
-            Some(format!("Max {}", multiplier).trim().to_string())
-        }
-        Some("pro") => Some("Pro".to_string()),
-        Some("free") | None => Some("Free".to_string()),
-        Some(other) => Some(other.to_string()),
-    }
+#[derive(Debug, Serialize, Clone)]
+pub struct UsageError {
+    pub account_id: String,
+    pub error: String,
 }
 
-fn get_claude_credentials() -> Result<CredentialsInfo, String> {
-    let json_str = get_raw_credentials_json()?;
-
-    let creds: ClaudeCredentials =
-        serde_json::from_str(&json_str).map_err(|e| format!("Failed to parse credentials: {}", e))?;
-
-    creds
-        .claude_ai_oauth
-        .map(|oauth| CredentialsInfo {
-            access_token: oauth.access_token,
-            refresh_token: oauth.refresh_token,
-            subscription: format_subscription(
-                oauth.subscription_type.as_deref(),
-                oauth.rate_limit_tier.as_deref(),
-            ),
-        })
-        .ok_or_else(|| "No OAuth token found in credentials".to_string())
+#[derive(Debug, Serialize, Clone)]
+pub struct AccountUpdate {
+    pub account_id: String,
+    pub logged_in: bool,
+    // Set when the profile was re-read and the tier differs from before
+    pub subscription_changed: bool,
+    // Absent after a logout
+    #[serde(flatten)]
+    pub account: Option<AccountInfo>,
 }
 
-fn get_claude_token() -> Result<String, String> {
-    get_claude_credentials().map(|c| c.access_token)
+pub struct AppState {
+    // One slot per configured account, in settings order
+    pub accounts: Arc<Mutex<Vec<AccountState>>>,
+    // What is in effect: the user's settings with managed settings layered on top
+    pub settings: Arc<Mutex<Settings>>,
+    // What is in the user's settings file
+    pub user_settings: Arc<Mutex<UserSettings>>,
+    pub managed: Arc<ManagedSettings>,
+    pub paused_until: Arc<Mutex<Option<DateTime<Utc>>>>,
+    pub poll_interval_changed: Arc<Notify>,
+    pub usage_service: Arc<usage_service::UsageService>,
+    // Why settings.toml can't be used. While set the app doesn't write the
+    // file, which would replace the user's edits with defaults
+    pub settings_error: Arc<Mutex<Option<String>>>,
+    // Problems found while starting, before the window listens for events,
+    // or found later but worth showing even so; the frontend asks for them
+    // once it has loaded
+    pub startup_errors: Arc<Mutex<Vec<String>>>,
 }
 
-fn get_refresh_token() -> Result<String, String> {
-    get_claude_credentials()
-        .and_then(|c| c.refresh_token.ok_or_else(|| "No refresh token found".to_string()))
+impl Default for AppState {
+    fn default() -> Self {
+        let settings = Settings::default();
+        let accounts = accounts::configured(&settings).iter().map(AccountState::new).collect();
+        Self {
+            accounts: Arc::new(Mutex::new(accounts)),
+            settings: Arc::new(Mutex::new(settings.clone())),
+            user_settings: Arc::new(Mutex::new(UserSettings { settings, ..UserSettings::default() })),
+            managed: Arc::new(ManagedSettings::default()),
+            paused_until: Arc::new(Mutex::new(None)),
+            poll_interval_changed: Arc::new(Notify::new()),
+            usage_service: Arc::new(usage_service::UsageService::default()),
+            settings_error: Arc::new(Mutex::new(None)),
+            startup_errors: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
 }
 
-async fn fetch_usage_internal(token: &str) -> Result<UsageResponse, (String, bool)> {
-    let client = reqwest::Client::new();
+async fn fetch_usage_internal(api: &ApiSettings, token: &str) -> Result<UsageResponse, (String, Failure)> {
+    let client = api.client().map_err(|e| (e, Failure::Other))?;
     let response = client
-        .get("https://api.anthropic.com/api/oauth/usage")
+        .get(api.url("/api/oauth/usage"))
         .header("Authorization", format!("Bearer {}", token))
         .header("anthropic-beta", "oauth-2025-04-20")
         .send()
         .await
-        .map_err(|e| (format!("Request failed: {}", e), false))?;
+        .map_err(|e| {
+            let failure = if e.is_connect() || e.is_timeout() { Failure::Offline } else { Failure::Other };
+            (format!("Request failed: {}", e), failure)
+        })?;
 
     let status = response.status();
     if !status.is_success() {
-        let is_auth_error = status.as_u16() == 401;
-        return Err((format!("API returned status: {}", status), is_auth_error));
+        let failure = match status.as_u16() {
+            401 => Failure::AuthRequired,
+            429 => Failure::RateLimited {
+                retry_after: response
+                    .headers()
+                    .get("retry-after")
+                    .and_then(|value| value.to_str().ok()?.parse().ok())
+                    .map(Duration::from_secs),
+            },
+            _ => Failure::Other,
+        };
+        return Err((format!("API returned status: {}", status), failure));
     }
 
     response
         .json::<UsageResponse>()
         .await
-        .map_err(|e| (format!("Failed to parse response: {}", e), false))
+        .map_err(|e| (format!("Failed to parse response: {}", e), Failure::Other))
 }
 
-async fn fetch_usage(token: &str) -> Result<UsageResponse, String> {
-    match fetch_usage_internal(token).await {
+async fn fetch_usage(api: &ApiSettings, source: &CredentialSource) -> Result<UsageResponse, (String, Failure)> {
+    let token = source.load_token().await.map_err(|e| {
+        let failure = if e == credentials::NOT_LOGGED_IN { Failure::AuthRequired } else { Failure::Other };
+        (e, failure)
+    })?;
+    match fetch_usage_internal(api, token.access_token.expose()).await {
         Ok(usage) => Ok(usage),
-        Err((err, is_auth_error)) => {
-            if is_auth_error {
+        Err((err, failure)) => {
+            if failure == Failure::AuthRequired {
                 // Try to refresh the token
-                if let Ok(refresh_token) = get_refresh_token() {
-                    if let Ok(new_token) = refresh_oauth_token(&refresh_token).await {
-                        // Retry with new token
-                        return fetch_usage_internal(&new_token.access_token)
-                            .await
-                            .map_err(|(e, _)| e);
-                    }
+                if let Ok(new_token) = credentials::refresh_oauth_token(api, source).await {
+                    // Retry with new token
+                    return fetch_usage_internal(api, new_token.access_token.expose()).await;
                 }
             }
-            Err(err)
+            Err((err, failure))
         }
     }
 }
 
-async fn fetch_profile_internal(token: &str) -> Result<AccountInfo, (String, bool)> {
-    let client = reqwest::Client::new();
+async fn fetch_profile_internal(api: &ApiSettings, token: &str) -> Result<AccountInfo, (String, bool)> {
+    let client = api.client().map_err(|e| (e, false))?;
     let response = client
-        .get("https://api.anthropic.com/api/oauth/profile")
+        .get(api.url("/api/oauth/profile"))
         .header("Authorization", format!("Bearer {}", token))
         .header("anthropic-beta", "oauth-2025-04-20")
         .send()
@@ -340,137 +227,332 @@ async fn fetch_profile_internal(token: &str) -> Result<AccountInfo, (String, boo
         email: Some(profile.account.email),
         display_name: profile.account.display_name,
         full_name: profile.account.full_name,
-        subscription: None, // Set by caller from credentials
+        subscription: None, // Set by fetch_profile from credentials
     })
 }
 
-async fn fetch_profile(token: &str) -> Result<AccountInfo, String> {
-    match fetch_profile_internal(token).await {
+async fn fetch_profile(api: &ApiSettings, source: &CredentialSource) -> Result<AccountInfo, String> {
+    let token = source.load_token().await?;
+    let subscription = Some(SubscriptionTier::parse(
+        token.subscription_type.as_deref(),
+        token.rate_limit_tier.as_deref(),
+    ));
+    let profile = match fetch_profile_internal(api, token.access_token.expose()).await {
         Ok(profile) => Ok(profile),
         Err((err, is_auth_error)) => {
             if is_auth_error {
                 // Try to refresh the token
-                if let Ok(refresh_token) = get_refresh_token() {
-                    if let Ok(new_token) = refresh_oauth_token(&refresh_token).await {
-                        // Retry with new token
-                        return fetch_profile_internal(&new_token.access_token)
-                            .await
-                            .map_err(|(e, _)| e);
-                    }
+                if let Ok(new_token) = credentials::refresh_oauth_token(api, source).await {
+                    // Retry with new token
+                    return fetch_profile_internal(api, new_token.access_token.expose())
+                        .await
+                        .map(|profile| AccountInfo { subscription, ..profile })
+                        .map_err(|(e, _)| e);
                 }
             }
             Err(err)
         }
-    }
+    }?;
+    Ok(AccountInfo { subscription, ..profile })
 }
 
-fn auto_select_chrome_profile(email: &str) -> Option<String> {
-    let profiles = get_chrome_profiles();
-    for profile in profiles {
-        if let Some(ref profile_email) = profile.email {
-            if profile_email.eq_ignore_ascii_case(email) {
-                // Save the matched profile
-                let home = std::env::var("HOME").unwrap_or_default();
-                let config_path = format!("{}/.claude-usage-monitor-profile", home);
-                let _ = std::fs::write(&config_path, &profile.id);
-                return Some(profile.id);
-            }
-        }
+// Maps the account to the browser profile signed in with the same email,
+// unless it already has a mapping
+async fn auto_select_browser_profile(app: &AppHandle, email: &str) -> Option<BrowserProfile> {
+    let selected = app.state::<AppState>().settings.lock().await.browser.clone();
+    if selected.mapping(email).is_some() {
+        return None;
     }
-    None
+    // Reads the browsers' profile files
+    let profile = tauri::async_runtime::spawn_blocking({
+        let (email, preferred) = (email.to_string(), selected.browser.clone());
+        move || browser::find_profile_by_email(&email, preferred.as_deref())
+    })
+    .await
+    .ok()??;
+    let mapped = Some((profile.browser.clone(), Some(profile.id.clone())));
+    let _ = change_settings(app, |settings| settings.browser.set_mapping(email, mapped)).await;
+    Some(profile)
 }
 
-fn update_tray_title(app: &AppHandle, usage: &UsageResponse) {
+// The account the tray follows, see accounts::displayed
+async fn displayed_account(app: &AppHandle) -> Option<AccountState> {
+    let state = app.state::<AppState>();
+    let settings = state.settings.lock().await.clone();
+    let accounts = state.accounts.lock().await;
+    accounts::displayed(&accounts, &settings).cloned()
+}
+
+async fn update_tray_title(app: &AppHandle) {
     if let Some(tray) = app.tray_by_id(&TrayIconId::new("main")) {
-        let title = if let Some(ref five_hour) = usage.five_hour {
-            format!("{}%", five_hour.utilization.unwrap_or(0.0) as i32)
-        } else {
-            "--%".to_string()
+        let Some(usage) = displayed_account(app).await.and_then(|account| account.usage) else {
+            // Nothing to show yet, or logged out
+            let _ = tray.set_title(Some("--%"));
+            return;
         };
+        let state = app.state::<AppState>();
+        let format = state.settings.lock().await.tray.format.clone();
+        // Settings are validated before they take effect
+        let template = Template::parse(&format).unwrap_or_default();
+        let title = template.render(&usage, chrono::Utc::now());
         let _ = tray.set_title(Some(&title));
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ChromeProfile {
-    pub id: String,
-    pub name: String,
-    pub email: Option<String>,
+async fn update_tray_tooltip(app: &AppHandle) {
+    if let Some(tray) = app.tray_by_id(&TrayIconId::new("main")) {
+        let data = tray_menu::TrayMenuData::from_state(&app.state::<AppState>()).await;
+        let tooltip = summary::tooltip_text(
+            data.usage.as_ref(),
+            data.account.as_ref(),
+            data.last_updated,
+            data.status.as_deref(),
+            data.last_error.as_deref(),
+            data.logged_out,
+        );
+        let _ = tray.set_tooltip(Some(&tooltip));
+    }
+}
+
+// The display the tray icon is on, when the platform says where it is
+fn tray_monitor(app: &AppHandle) -> Option<tauri::Monitor> {
+    let on_tray = app
+        .tray_by_id(&TrayIconId::new("main"))
+        .and_then(|tray| tray.rect().ok().flatten())
+        .and_then(|rect| {
+            let position = rect.position.to_physical::<f64>(1.0);
+            app.monitor_from_point(position.x, position.y).ok().flatten()
+        });
+    on_tray.or_else(|| app.primary_monitor().ok().flatten())
+}
+
+async fn update_tray_icon(app: &AppHandle) {
+    if let Some(tray) = app.tray_by_id(&TrayIconId::new("main")) {
+        let account = displayed_account(app).await;
+        // Whatever usage is still shown isn't current
+        let stale = account.as_ref().is_some_and(|account| account.failure.is_some() || !account.logged_in);
+        let usage = account.and_then(|account| account.usage);
+        let state = app.state::<AppState>();
+        let (settings, thresholds) = {
+            let settings = state.settings.lock().await;
+            (settings.tray.icon.clone(), settings.thresholds.clone())
+        };
+
+        if settings.style == IconStyle::Static {
+            let _ = tray.set_icon(Some(tray_icon::static_icon()));
+            let _ = tray.set_icon_as_template(false);
+            return;
+        }
+
+        let scale = tray_monitor(app).map(|monitor| monitor.scale_factor()).unwrap_or(2.0);
+        let size = (tray_icon::ICON_POINTS * scale).round() as u32;
+
+        let utilization = |limit: Option<&UsageLimit>| limit.and_then(|l| l.utilization);
+        let icon = tray_icon::render_gauge(
+            &settings,
+            &thresholds,
+            utilization(usage.as_ref().and_then(|u| u.five_hour.as_ref())),
+            utilization(usage.as_ref().and_then(|u| u.seven_day.as_ref())),
+            stale,
+            size,
+        );
+        let _ = tray.set_icon(Some(tray_icon::to_tauri_image(icon)));
+        let _ = tray.set_icon_as_template(settings.monochrome);
+    }
+}
+
+// Re-renders everything in the tray after any account changed
+async fn update_tray(app: &AppHandle) {
+    update_tray_title(app).await;
+    update_tray_icon(app).await;
+    tray_menu::update_tray_menu(app).await;
+    update_tray_tooltip(app).await;
+}
+
+// Account slot by id, or the active one
+async fn account_settings(state: &AppState, account_id: Option<&str>) -> Result<AccountSettings, String> {
+    let settings = state.settings.lock().await;
+    let id = account_id.map(str::to_string).unwrap_or_else(|| accounts::active_id(&settings));
+    accounts::configured(&settings)
+        .into_iter()
+        .find(|account| account.id == id)
+        .ok_or_else(|| format!("No account with id '{}'", id))
+}
+
+async fn account_state(state: &AppState, account_id: Option<String>) -> Result<AccountState, String> {
+    let id = account_settings(state, account_id.as_deref()).await?.id;
+    state
+        .accounts
+        .lock()
+        .await
+        .iter()
+        .find(|account| account.id == id)
+        .cloned()
+        .ok_or_else(|| format!("No account with id '{}'", id))
 }
 
 #[tauri::command]
-fn get_chrome_profiles() -> Vec<ChromeProfile> {
-    let mut profiles = Vec::new();
-    let home = std::env::var("HOME").unwrap_or_default();
-    let chrome_path = format!("{}/Library/Application Support/Google/Chrome", home);
-
-    if let Ok(entries) = std::fs::read_dir(&chrome_path) {
-        for entry in entries.flatten() {
-            let name = entry.file_name().to_string_lossy().to_string();
-            if name == "Default" || name.starts_with("Profile ") {
-                let prefs_path = format!("{}/{}/Preferences", chrome_path, name);
-                if let Ok(content) = std::fs::read_to_string(&prefs_path) {
-                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(&content) {
-                        let profile_name = json["profile"]["name"]
-                            .as_str()
-                            .unwrap_or(&name)
-                            .to_string();
-                        let email = json["account_info"]
-                            .as_array()
-                            .and_then(|arr| arr.first())
-                            .and_then(|acc| acc["email"].as_str())
-                            .map(|s| s.to_string());
-
-                        profiles.push(ChromeProfile {
-                            id: name,
-                            name: profile_name,
-                            email,
-                        });
-                    }
-                }
-            }
+fn get_browser_profiles() -> Vec<BrowserProfile> {
+    browser::all_profiles()
+}
+
+#[tauri::command]
+async fn get_selected_profile(state: tauri::State<'_, AppState>) -> Result<BrowserSettings, String> {
+    Ok(state.settings.lock().await.browser.clone())
+}
+
+// Sets the profile for the account with this email, or the one used for
+// accounts without a mapping when no email is given
+#[tauri::command]
+async fn set_selected_profile(
+    browser: String,
+    profile_id: String,
+    email: Option<String>,
+    app: AppHandle,
+) -> Result<(), String> {
+    // Only a profile the browser actually has, since the id ends up on its command line
+    let known = tauri::async_runtime::spawn_blocking({
+        let (browser, profile_id) = (browser.clone(), profile_id.clone());
+        move || browser::all_profiles().iter().any(|p| p.browser == browser && p.id == profile_id)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    if !known {
+        return Err(format!("'{}' is not a profile of browser '{}'", profile_id, browser));
+    }
+    change_settings(&app, |settings| match email {
+        Some(email) => settings.browser.set_mapping(&email, Some((browser, Some(profile_id)))),
+        None => {
+            settings.browser.browser = Some(browser);
+            settings.browser.profile = Some(profile_id);
+        }
+    })
+    .await
+}
+
+// Back to the default profile for this account. The choice is kept as a
+// mapping so the next profile sync doesn't match the account by email again.
+#[tauri::command]
+async fn clear_selected_profile(email: String, app: AppHandle) -> Result<(), String> {
+    change_settings(&app, |settings| settings.browser.set_mapping(&email, None)).await
+}
+
+// Email of the active account, once its profile has been fetched
+async fn active_email(state: &AppState) -> Option<String> {
+    let active = accounts::active_id(&*state.settings.lock().await);
+    let accounts = state.accounts.lock().await;
+    let account = accounts.iter().find(|account| account.id == active)?;
+    account.account.as_ref()?.email.clone()
+}
+
+// How open_url opened a link, so the UI can say when the chosen profile wasn't used
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "route", rename_all = "snake_case")]
+pub enum OpenRoute {
+    Profile { browser: String, profile: Option<String> },
+    SystemDefault { reason: String },
+}
+
+#[tauri::command]
+async fn open_url(url: String, state: tauri::State<'_, AppState>, app: AppHandle) -> Result<OpenRoute, String> {
+    let selected = state.settings.lock().await.browser.clone();
+    let url = browser::check_url(&url, &selected.allowed_hosts)?;
+    let (browser_id, profile) = selected.profile_for(active_email(&state).await.as_deref());
+    // Finding the browser and checking the profile read its files
+    let opened = tauri::async_runtime::spawn_blocking({
+        let (browser_id, profile, url) = (browser_id.clone(), profile.clone(), url.clone());
+        move || {
+            browser::find(&browser_id)
+                .ok_or_else(|| format!("Browser '{}' is not installed", browser_id))
+                .and_then(|provider| provider.open_url(profile.as_deref(), &url))
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|opened| opened);
+
+    match opened {
+        Ok(()) => Ok(OpenRoute::Profile { browser: browser_id, profile }),
+        Err(reason) => {
+            app.opener()
+                .open_url(&url, None::<&str>)
+                .map_err(|e| format!("{}; the default browser failed too: {}", reason, e))?;
+            Ok(OpenRoute::SystemDefault { reason })
         }
     }
-    profiles
 }
 
 #[tauri::command]
-fn get_selected_profile() -> Option<String> {
-    let home = std::env::var("HOME").unwrap_or_default();
-    let config_path = format!("{}/.claude-usage-monitor-profile", home);
-    std::fs::read_to_string(&config_path).ok()
+async fn get_usage(account_id: Option<String>, state: tauri::State<'_, AppState>) -> Result<Option<UsageResponse>, String> {
+    Ok(account_state(&state, account_id).await?.usage)
+}
+
+#[tauri::command]
+async fn get_account(account_id: Option<String>, state: tauri::State<'_, AppState>) -> Result<Option<AccountInfo>, String> {
+    Ok(account_state(&state, account_id).await?.account)
 }
 
 #[tauri::command]
-fn set_selected_profile(profile_id: String) {
-    let home = std::env::var("HOME").unwrap_or_default();
-    let config_path = format!("{}/.claude-usage-monitor-profile", home);
-    let _ = std::fs::write(&config_path, &profile_id);
+async fn get_accounts(state: tauri::State<'_, AppState>) -> Result<Vec<AccountState>, String> {
+    Ok(state.accounts.lock().await.clone())
 }
 
 #[tauri::command]
-fn open_url(url: String) {
-    let home = std::env::var("HOME").unwrap_or_default();
-    let config_path = format!("{}/.claude-usage-monitor-profile", home);
-    let profile = std::fs::read_to_string(&config_path)
-        .unwrap_or_else(|_| "Default".to_string());
+async fn get_active_account(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    Ok(accounts::active_id(&*state.settings.lock().await))
+}
 
-    // Use Chrome binary directly to ensure profile is respected even when Chrome is running
-    let _ = std::process::Command::new("/Applications/Google Chrome.app/Contents/MacOS/Google Chrome")
-        .args([&format!("--profile-directory={}", profile), &url])
-        .spawn();
+#[tauri::command]
+async fn set_active_account(account_id: String, state: tauri::State<'_, AppState>, app: AppHandle) -> Result<(), String> {
+    let id = account_settings(&state, Some(&account_id)).await?.id;
+    change_settings(&app, |settings| settings.active_account = Some(id)).await
 }
 
+// Signs in with the browser, without Claude Code, and monitors that login
 #[tauri::command]
-async fn get_usage(state: tauri::State<'_, AppState>) -> Result<Option<UsageResponse>, String> {
-    let usage = state.usage.lock().await;
-    Ok(usage.clone())
+async fn oauth_login(app: AppHandle) -> Result<(), String> {
+    let api = app.state::<AppState>().settings.lock().await.api.clone();
+    let opener = app.clone();
+    oauth::login(&api, &oauth::OAuthEndpoints::default(), &CredentialSource::App, |url| {
+        opener
+            .opener()
+            .open_url(url, None::<&str>)
+            .map_err(|e| format!("Failed to open the browser: {}", e))
+    })
+    .await?;
+
+    let existing = accounts::app_account(&*app.state::<AppState>().settings.lock().await);
+    let claude_code_signed_in = matches!(CredentialSource::ClaudeCode.load_token_opt().await, Ok(Some(_)));
+    change_settings(&app, |settings| accounts::add_app_account(settings, claude_code_signed_in)).await?;
+    // A new account is checked when its poller starts. An existing one, e.g.
+    // signed out earlier, isn't: its watcher skips the app's own write
+    if let Some(account) = existing {
+        usage_service::check_credentials(&app, &account).await;
+    }
+    Ok(())
 }
 
+// Signs out of the app's own login; the account stays configured and shows
+// as logged out
 #[tauri::command]
-async fn get_account(state: tauri::State<'_, AppState>) -> Result<Option<AccountInfo>, String> {
-    let account = state.account.lock().await;
-    Ok(account.clone())
+async fn oauth_logout(app: AppHandle) -> Result<(), String> {
+    let api = app.state::<AppState>().settings.lock().await.api.clone();
+    oauth::logout(&api, &oauth::OAuthEndpoints::default(), &CredentialSource::App).await
+}
+
+// Redacted token details per account, for diagnosing auth problems
+#[tauri::command]
+async fn inspect_tokens(state: tauri::State<'_, AppState>) -> Result<Vec<inspect::TokenReport>, String> {
+    let configured = accounts::configured(&*state.settings.lock().await);
+    // Reading the keychain runs `security`
+    tauri::async_runtime::spawn_blocking(move || configured.iter().map(inspect::inspect).collect())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_history(account_id: Option<String>, state: tauri::State<'_, AppState>) -> Result<Vec<HistoryEntry>, String> {
+    Ok(account_state(&state, account_id).await?.history.into_iter().collect())
 }
 
 #[tauri::command]
@@ -483,53 +565,217 @@ async fn set_window_height(app: AppHandle, height: u32) -> Result<(), String> {
 }
 
 #[tauri::command]
-async fn get_last_error(state: tauri::State<'_, AppState>) -> Result<Option<String>, String> {
-    let error = state.last_error.lock().await;
-    Ok(error.clone())
+async fn get_last_error(account_id: Option<String>, state: tauri::State<'_, AppState>) -> Result<Option<String>, String> {
+    Ok(account_state(&state, account_id).await?.last_error)
+}
+
+#[tauri::command]
+async fn get_status(account_id: Option<String>, state: tauri::State<'_, AppState>) -> Result<MonitorStatus, String> {
+    let interval = Duration::from_secs(state.settings.lock().await.polling.interval_secs);
+    let slot = account_state(&state, account_id).await?;
+    Ok(MonitorStatus::new(&slot, interval, Utc::now()))
 }
 
 #[tauri::command]
-async fn refresh_usage(state: tauri::State<'_, AppState>, app: AppHandle) -> Result<(), String> {
-    let token = get_claude_token()?;
-    match fetch_usage(&token).await {
-        Ok(usage) => {
-            update_tray_title(&app, &usage);
-            *state.usage.lock().await = Some(usage.clone());
-            *state.last_error.lock().await = None;
-            let _ = app.emit("usage-updated", usage);
+async fn get_settings(state: tauri::State<'_, AppState>) -> Result<Settings, String> {
+    Ok(state.settings.lock().await.clone())
+}
+
+fn apply_autostart(old: &Settings, new: &Settings) -> Result<(), String> {
+    if new.autostart.enabled != old.autostart.enabled || new.autostart.method != old.autostart.method {
+        if old.autostart.method != new.autostart.method {
+            autostart::disable(old.autostart.method)?;
         }
-        Err(e) => {
-            *state.last_error.lock().await = Some(e.clone());
-            let _ = app.emit("usage-error", e.clone());
-            return Err(e);
+        if new.autostart.enabled {
+            autostart::enable(new.autostart.method)?;
+        } else {
+            autostart::disable(new.autostart.method)?;
         }
     }
     Ok(())
 }
 
-async fn start_polling(app: AppHandle, state: Arc<Mutex<Option<UsageResponse>>>, error_state: Arc<Mutex<Option<String>>>) {
-    let mut ticker = interval(Duration::from_secs(60));
-    loop {
-        ticker.tick().await;
-        match get_claude_token() {
-            Ok(token) => match fetch_usage(&token).await {
-                Ok(usage) => {
-                    update_tray_title(&app, &usage);
-                    *state.lock().await = Some(usage.clone());
-                    *error_state.lock().await = None;
-                    let _ = app.emit("usage-updated", usage);
-                }
-                Err(e) => {
-                    *error_state.lock().await = Some(e.clone());
-                    let _ = app.emit("usage-error", e);
-                }
-            },
-            Err(e) => {
-                *error_state.lock().await = Some(e.clone());
-                let _ = app.emit("usage-error", e);
-            }
+// Applies the side effects of a settings change: shortcuts, the login entry
+// and everything rendered from settings. If the new shortcuts can't be
+// registered or the login entry can't be changed, fails with the old
+// shortcuts registered again.
+async fn apply_settings(app: &AppHandle, old: &Settings, new: &Settings) -> Result<(), String> {
+    let hotkeys_changed = new.hotkeys != old.hotkeys;
+    let registered = if hotkeys_changed { hotkeys::register_hotkeys(app, &new.hotkeys) } else { Ok(()) };
+    if let Err(e) = registered.and_then(|()| apply_autostart(old, new)) {
+        if hotkeys_changed {
+            let _ = hotkeys::register_hotkeys(app, &old.hotkeys);
+        }
+        return Err(e);
+    }
+
+    if new.polling != old.polling {
+        app.state::<AppState>().poll_interval_changed.notify_waiters();
+    }
+
+    if new.accounts != old.accounts {
+        let state = app.state::<AppState>();
+        let configured = accounts::configured(new);
+        let mut slots = state.accounts.lock().await;
+        *slots = accounts::reconcile(&slots, &configured);
+        drop(slots);
+        state.usage_service.start(app, new);
+    }
+
+    update_tray(app).await;
+    let _ = app.emit("settings-updated", new.clone());
+    Ok(())
+}
+
+// Replaces the user's settings, resolving managed settings on top and
+// applying the result. Nothing changes if that fails. `user` is the locked
+// user_settings, held by the caller for the whole change so that concurrent
+// changes can't overwrite each other
+async fn store_user_settings(app: &AppHandle, user: &mut UserSettings, new_user: UserSettings) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let effective = state.managed.resolve(&new_user)?.settings;
+    effective.validate()?;
+
+    let old = std::mem::replace(&mut *state.settings.lock().await, effective.clone());
+    if let Err(e) = apply_settings(app, &old, &effective).await {
+        *state.settings.lock().await = old;
+        return Err(e);
+    }
+    *user = new_user;
+    Ok(())
+}
+
+// Edits settings as the user sees them and saves what the user changed.
+// Fails if the edit touches a setting locked by managed settings.
+async fn change_settings(app: &AppHandle, edit: impl FnOnce(&mut Settings)) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let mut user = state.user_settings.lock().await;
+    if let Some(ref e) = *state.settings_error.lock().await {
+        return Err(format!("Settings can't be changed until settings.toml is fixed. {}", e));
+    }
+    let old = state.settings.lock().await.clone();
+    let mut edited = old.clone();
+    edit(&mut edited);
+    edited.validate()?;
+
+    let new_user = state.managed.user_changes(&user, &old, &edited)?;
+    if new_user == *user {
+        return Ok(());
+    }
+    settings::save_settings(&new_user)?;
+    if let Err(e) = store_user_settings(app, &mut user, new_user).await {
+        // Roll back so the file and the running app stay in agreement
+        settings::save_settings(&user)?;
+        return Err(e);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn update_settings(new_settings: Settings, app: AppHandle) -> Result<(), String> {
+    app.state::<AppState>().settings.lock().await.check_webview_edit(&new_settings)?;
+    change_settings(&app, |settings| {
+        let mut new_settings = new_settings;
+        // In case the file changed them meanwhile
+        settings.keep_file_only(&mut new_settings);
+        *settings = new_settings;
+    })
+    .await
+}
+
+#[tauri::command]
+async fn get_startup_errors(state: tauri::State<'_, AppState>) -> Result<Vec<String>, String> {
+    let settings_error = state.settings_error.lock().await.clone();
+    Ok(settings_error.into_iter().chain(state.startup_errors.lock().await.iter().cloned()).collect())
+}
+
+#[tauri::command]
+async fn get_effective_settings(state: tauri::State<'_, AppState>) -> Result<EffectiveSettings, String> {
+    let user = state.user_settings.lock().await.clone();
+    state.managed.resolve(&user)
+}
+
+#[tauri::command]
+async fn get_tray_format(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    Ok(state.settings.lock().await.tray.format.clone())
+}
+
+#[tauri::command]
+async fn set_tray_format(format: String, app: AppHandle) -> Result<(), String> {
+    Template::parse(&format)?;
+    change_settings(&app, |settings| settings.tray.format = format).await
+}
+
+#[tauri::command]
+async fn preview_tray_format(format: String, state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let template = Template::parse(&format)?;
+    let settings = state.settings.lock().await.clone();
+    let usage = accounts::displayed(&state.accounts.lock().await, &settings)
+        .and_then(|account| account.usage.clone())
+        .unwrap_or_default();
+    Ok(template.render(&usage, chrono::Utc::now()))
+}
+
+#[tauri::command]
+async fn get_tray_icon_settings(state: tauri::State<'_, AppState>) -> Result<TrayIconSettings, String> {
+    Ok(state.settings.lock().await.tray.icon.clone())
+}
+
+#[tauri::command]
+async fn set_tray_icon_settings(tray_icon: TrayIconSettings, app: AppHandle) -> Result<(), String> {
+    change_settings(&app, |settings| settings.tray.icon = tray_icon).await
+}
+
+#[tauri::command]
+async fn get_hotkeys(state: tauri::State<'_, AppState>) -> Result<HotkeySettings, String> {
+    Ok(state.settings.lock().await.hotkeys.clone())
+}
+
+#[tauri::command]
+async fn set_hotkeys(hotkeys: HotkeySettings, app: AppHandle) -> Result<(), String> {
+    // apply_settings puts the previous shortcuts back if these can't be registered
+    change_settings(&app, |settings| settings.hotkeys = hotkeys).await
+}
+
+#[tauri::command]
+async fn get_autostart(state: tauri::State<'_, AppState>) -> Result<AutostartSettings, String> {
+    let mut autostart = state.settings.lock().await.autostart.clone();
+    // Report what is actually installed, in case the entry was removed by hand
+    autostart.enabled = autostart::is_installed(autostart.method);
+    Ok(autostart)
+}
+
+#[tauri::command]
+async fn set_autostart(enabled: bool, method: Option<AutostartMethod>, app: AppHandle) -> Result<(), String> {
+    change_settings(&app, |settings| {
+        let method = method.unwrap_or(settings.autostart.method);
+        settings.autostart = AutostartSettings { enabled, method, prompted: true };
+    })
+    .await?;
+
+    // Settings may be unchanged while the entry was removed by hand
+    let autostart = app.state::<AppState>().settings.lock().await.autostart.clone();
+    if autostart.enabled {
+        autostart::enable(autostart.method)
+    } else {
+        autostart::disable(autostart.method)
+    }
+}
+
+// Refreshes one account, or all of them when no id is given
+#[tauri::command]
+async fn refresh_usage(account_id: Option<String>, state: tauri::State<'_, AppState>, app: AppHandle) -> Result<(), String> {
+    let targets = match account_id {
+        Some(id) => vec![account_settings(&state, Some(&id)).await?],
+        None => accounts::configured(&*state.settings.lock().await),
+    };
+    let mut result = Ok(());
+    for account in targets {
+        if let Err(e) = state.usage_service.refresh(&app, &account).await {
+            result = result.and(Err(e));
         }
     }
+    result
 }
 
 fn toggle_window(app: &AppHandle, tray_x: f64, tray_y: f64) {
@@ -548,41 +794,133 @@ fn toggle_window(app: &AppHandle, tray_x: f64, tray_y: f64) {
     }
 }
 
+// Used when there is no click position, e.g. from a hotkey: anchor below the
+// tray icon if the platform reports where it is, otherwise top-right of the screen
+fn toggle_window_at_tray(app: &AppHandle) {
+    let monitor = tray_monitor(app);
+    let scale = monitor.as_ref().map(|m| m.scale_factor()).unwrap_or(1.0);
+
+    let tray_rect = app
+        .tray_by_id(&TrayIconId::new("main"))
+        .and_then(|tray| tray.rect().ok().flatten());
+    if let Some(rect) = tray_rect {
+        let position = rect.position.to_physical::<f64>(scale);
+        let size = rect.size.to_physical::<f64>(scale);
+        toggle_window(app, position.x + size.width / 2.0, position.y + size.height);
+    } else if let Some(monitor) = monitor {
+        let right = monitor.position().x as f64 + monitor.size().width as f64;
+        let top = monitor.position().y as f64;
+        toggle_window(app, right - 100.0 * scale, top + 24.0 * scale);
+    }
+}
+
+// `inspect-token [--json]`: prints what inspect_tokens returns
+fn cli_inspect_token(json: bool) -> i32 {
+    let settings = match settings::load_settings()
+        .and_then(|user| managed::load_managed_settings()?.resolve(&user))
+    {
+        Ok(effective) => effective.settings,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let reports: Vec<_> = accounts::configured(&settings).iter().map(inspect::inspect).collect();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports).unwrap_or_default());
+    } else {
+        for report in &reports {
+            println!("{}", report);
+        }
+    }
+    if reports.iter().any(|report| report.error.is_some()) { 1 } else { 0 }
+}
+
+// Command-line subcommands, handled before any window or tray is created.
+// Returns the exit code when the arguments name one
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    match args.first().map(String::as_str) {
+        Some("inspect-token") => {
+            attach_console();
+            Some(cli_inspect_token(args[1..].iter().any(|arg| arg == "--json")))
+        }
+        _ => None,
+    }
+}
+
+// Windows release builds are GUI programs without a console (see main.rs),
+// so subcommands print to the console of whoever ran them
+#[cfg(windows)]
+fn attach_console() {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    // Fails when there is no parent console or one is attached already,
+    // and either way there is nothing better to print to
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let app_state = AppState::default();
-    let usage_state = app_state.usage.clone();
-    let error_state = app_state.last_error.clone();
-    let account_state = app_state.account.clone();
+    // A broken settings file is reported once the app is up; defaults are
+    // used meanwhile, and the file is left alone until it is fixed
+    let (user_settings, settings_error) = match settings::load_settings() {
+        Ok(settings) => (settings, None),
+        Err(e) => (UserSettings::default(), Some(e)),
+    };
+    let (managed, managed_error) = match managed::load_managed_settings() {
+        Ok(managed) => (managed, None),
+        Err(e) => (ManagedSettings::default(), Some(e)),
+    };
+    // Checked like any later change, since managed settings can break
+    // settings that are valid on their own. The user's settings, validated
+    // when loaded, stand in meanwhile
+    let effective = managed.resolve(&user_settings).and_then(|effective| {
+        effective.settings.validate()?;
+        Ok(effective.settings)
+    });
+    let (initial_settings, resolve_error) = match effective {
+        Ok(settings) => (settings, None),
+        Err(e) => (user_settings.settings.clone(), Some(e)),
+    };
+    let initial_accounts = accounts::configured(&initial_settings).iter().map(AccountState::new).collect();
+    let app_state = AppState {
+        accounts: Arc::new(Mutex::new(initial_accounts)),
+        settings: Arc::new(Mutex::new(initial_settings)),
+        user_settings: Arc::new(Mutex::new(user_settings)),
+        managed: Arc::new(managed),
+        settings_error: Arc::new(Mutex::new(settings_error)),
+        startup_errors: Arc::new(Mutex::new(managed_error.into_iter().chain(resolve_error).collect())),
+        ..AppState::default()
+    };
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_global_shortcut::Builder::new().build())
         .manage(app_state)
         .setup(move |app| {
             // Hide from dock
             #[cfg(target_os = "macos")]
-            app.set_activation_policy(ActivationPolicy::Accessory);
+            app.set_activation_policy(tauri::ActivationPolicy::Accessory);
 
-            let quit = MenuItemBuilder::with_id("quit", "Quit").build(app)?;
-            let menu = MenuBuilder::new(app).item(&quit).build()?;
-
-            let icon_bytes = include_bytes!("../icons/32x32.png");
-            let icon_image = image::load_from_memory(icon_bytes)
-                .expect("Failed to load icon")
-                .to_rgba8();
-            let (width, height) = icon_image.dimensions();
-            let icon = Image::new_owned(icon_image.into_raw(), width, height);
+            let menu = tray_menu::build_menu(app.handle(), &tray_menu::TrayMenuData::default())?;
 
             let _tray = TrayIconBuilder::with_id("main")
-                .icon(icon)
+                .icon(tray_icon::static_icon())
                 .menu(&menu)
                 .menu_on_left_click(false)
                 .title("--%")
                 .tooltip("Claude Usage Monitor")
                 .on_menu_event(|app, event| {
-                    if event.id().as_ref() == "quit" {
-                        app.exit(0);
-                    }
+                    tray_menu::handle_menu_event(app, event.id().as_ref());
                 })
                 .on_tray_icon_event(|tray, event| {
                     if let tauri::tray::TrayIconEvent::Click {
@@ -596,51 +934,26 @@ pub fn run() {
                 })
                 .build(app)?;
 
-            // Initial fetch
-            let app_handle = app.handle().clone();
-            let usage_clone = usage_state.clone();
-            let error_clone = error_state.clone();
-            let account_clone = account_state.clone();
-
-            tauri::async_runtime::spawn(async move {
-                match get_claude_credentials() {
-                    Ok(creds) => {
-                        // Fetch profile and auto-select Chrome profile
-                        if let Ok(mut account) = fetch_profile(&creds.access_token).await {
-                            account.subscription = creds.subscription;
-                            if let Some(ref email) = account.email {
-                                auto_select_chrome_profile(email);
-                            }
-                            *account_clone.lock().await = Some(account.clone());
-                            let _ = app_handle.emit("account-updated", account);
-                        }
-
-                        // Fetch usage
-                        match fetch_usage(&creds.access_token).await {
-                            Ok(usage) => {
-                                update_tray_title(&app_handle, &usage);
-                                *usage_clone.lock().await = Some(usage.clone());
-                                let _ = app_handle.emit("usage-updated", usage);
-                            }
-                            Err(e) => {
-                                *error_clone.lock().await = Some(e.clone());
-                                let _ = app_handle.emit("usage-error", e);
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        *error_clone.lock().await = Some(e.clone());
-                        let _ = app_handle.emit("usage-error", e);
-                    }
-                }
-            });
+            let startup_settings = app.state::<AppState>().settings.blocking_lock().clone();
+            if let Err(e) = hotkeys::register_hotkeys(app.handle(), &startup_settings.hotkeys) {
+                // E.g. a shortcut another app already holds
+                app.state::<AppState>().startup_errors.blocking_lock().push(e);
+            }
+
+            if let Err(e) = settings_watcher::watch_settings(app.handle().clone()) {
+                app.state::<AppState>().startup_errors.blocking_lock().push(e);
+            }
+
+            // Keep the login entry pointing at this executable if the app was moved
+            if startup_settings.autostart.enabled {
+                let _ = autostart::enable(startup_settings.autostart.method);
+            }
 
-            let app_handle = app.handle().clone();
-            tauri::async_runtime::spawn(start_polling(app_handle, usage_state, error_state));
+            app.state::<AppState>().usage_service.start(app.handle(), &startup_settings);
 
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![get_usage, get_last_error, refresh_usage, open_url, get_chrome_profiles, get_selected_profile, set_selected_profile, set_window_height, get_account])
+        .invoke_handler(tauri::generate_handler![get_usage, get_last_error, get_status, refresh_usage, get_accounts, get_active_account, set_active_account, oauth_login, oauth_logout, inspect_tokens, get_history, open_url, get_browser_profiles, get_selected_profile, set_selected_profile, clear_selected_profile, set_window_height, get_account, get_tray_format, set_tray_format, preview_tray_format, get_tray_icon_settings, set_tray_icon_settings, get_hotkeys, set_hotkeys, get_autostart, set_autostart, get_settings, update_settings, get_startup_errors, get_effective_settings])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
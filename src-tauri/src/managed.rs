@@ -0,0 +1,249 @@
+// Admin-managed configuration for fleet deployments, read from a system path
+// users can't write to:
+//
+//   macOS: /Library/Application Support/claude-usage-monitor/managed.toml
+//   Linux: /etc/claude-usage-monitor/managed.toml
+//
+// The file mirrors settings.toml under a [settings] table. Values there are
+// defaults the user may change, unless their key (or a parent key) is listed
+// in `locked`, in which case they always win:
+//
+//   locked = ["api", "browser.allowed_hosts"]
+//   min_poll_interval_secs = 120
+//
+//   [settings.api]
+//   base_url = "https://claude-gateway.example.com"
+//   proxy = "http://proxy.example.com:3128"
+//
+// A locked key without a managed value pins the built-in default.
+
+use crate::settings::{flatten, to_table, unflatten, Settings, UserSettings};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingSource {
+    Default,
+    User,
+    ManagedDefault,
+    Managed,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ManagedSettings {
+    settings: toml::Table,
+    locked: Vec<String>,
+    min_poll_interval_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EffectiveSettings {
+    pub settings: Settings,
+    // Dotted key (e.g. "polling.interval_secs") to where its value came from
+    pub sources: BTreeMap<String, SettingSource>,
+    pub locked: Vec<String>,
+}
+
+pub fn managed_settings_path() -> PathBuf {
+    if cfg!(target_os = "macos") {
+        PathBuf::from("/Library/Application Support/claude-usage-monitor/managed.toml")
+    } else {
+        PathBuf::from("/etc/claude-usage-monitor/managed.toml")
+    }
+}
+
+// No managed file means nothing is managed
+pub fn load_managed_settings() -> Result<ManagedSettings, String> {
+    let path = managed_settings_path();
+    match std::fs::read_to_string(&path) {
+        Ok(content) => toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse managed settings {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ManagedSettings::default()),
+        Err(e) => Err(format!("Failed to read managed settings {}: {}", path.display(), e)),
+    }
+}
+
+impl ManagedSettings {
+    pub fn is_locked(&self, key: &str) -> bool {
+        self.locked
+            .iter()
+            .any(|locked| key == locked || key.starts_with(&format!("{}.", locked)))
+    }
+
+    // Turns settings edited from the effective view back into user settings.
+    // Values left as shown keep the user's own value, so managed values are
+    // never copied into the user's file; changed values become set by the
+    // user. Changing a locked value is an error.
+    pub fn user_changes(&self, user: &UserSettings, effective: &Settings, edited: &Settings) -> Result<UserSettings, String> {
+        let mut user_values = BTreeMap::new();
+        let mut effective_values = BTreeMap::new();
+        let mut edited_values = BTreeMap::new();
+        flatten(&to_table(&user.settings)?, "", &mut user_values);
+        flatten(&to_table(effective)?, "", &mut effective_values);
+        flatten(&to_table(edited)?, "", &mut edited_values);
+
+        let mut leaves = user_values;
+        let mut keys = user.keys.clone();
+        // Optional values that were cleared, e.g. an unset hotkey
+        for key in effective_values.keys().filter(|key| !edited_values.contains_key(*key)) {
+            if self.is_locked(key) {
+                return Err(format!("'{}' is locked by your administrator", key));
+            }
+            leaves.remove(key);
+            keys.remove(key);
+        }
+        for (key, value) in edited_values {
+            if effective_values.get(&key) == Some(&value) {
+                continue;
+            }
+            if self.is_locked(&key) {
+                return Err(format!("'{}' is locked by your administrator", key));
+            }
+            keys.insert(key.clone());
+            leaves.insert(key, value);
+        }
+
+        let settings = toml::Value::Table(unflatten(leaves))
+            .try_into()
+            .map_err(|e| format!("Invalid settings: {}", e))?;
+        Ok(UserSettings { settings, keys })
+    }
+
+    // Layers built-in defaults, managed defaults, the user's settings and
+    // locked managed values, in increasing priority. Only keys the user's
+    // file sets count as the user's; managed defaults apply to the rest.
+    pub fn resolve(&self, user: &UserSettings) -> Result<EffectiveSettings, String> {
+        let mut defaults = BTreeMap::new();
+        let mut user_values = BTreeMap::new();
+        let mut managed = BTreeMap::new();
+        flatten(&to_table(&Settings::default())?, "", &mut defaults);
+        flatten(&to_table(&user.settings)?, "", &mut user_values);
+        user_values.retain(|key, _| user.keys.contains(key));
+        flatten(&self.settings, "", &mut managed);
+
+        let mut keys: Vec<&String> =
+            defaults.keys().chain(user_values.keys()).chain(managed.keys()).collect();
+        keys.sort();
+        keys.dedup();
+
+        let mut leaves = BTreeMap::new();
+        let mut sources = BTreeMap::new();
+        for key in keys {
+            let default = defaults.get(key);
+            let user_value = user_values.get(key);
+            let managed_value = managed.get(key);
+
+            let (value, source) = if self.is_locked(key) {
+                (managed_value.or(default), SettingSource::Managed)
+            } else if let Some(value) = user_value {
+                (Some(value), SettingSource::User)
+            } else if let Some(value) = managed_value {
+                (Some(value), SettingSource::ManagedDefault)
+            } else {
+                (default, SettingSource::Default)
+            };
+
+            if let Some(value) = value {
+                leaves.insert(key.clone(), value.clone());
+            }
+            sources.insert(key.clone(), source);
+        }
+
+        let mut settings: Settings = toml::Value::Table(unflatten(leaves))
+            .try_into()
+            .map_err(|e| format!("Invalid managed settings: {}", e))?;
+
+        if let Some(min) = self.min_poll_interval_secs {
+            if settings.polling.interval_secs < min {
+                settings.polling.interval_secs = min;
+                sources.insert("polling.interval_secs".to_string(), SettingSource::Managed);
+            }
+        }
+
+        Ok(EffectiveSettings { settings, sources, locked: self.locked.clone() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::parse_user_settings;
+
+    const MANAGED: &str = r#"
+locked = ["api"]
+min_poll_interval_secs = 120
+
+[settings.api]
+base_url = "https://claude-gateway.example.com"
+
+[settings.polling]
+interval_secs = 300
+profile_interval_secs = 3600
+"#;
+
+    fn managed() -> ManagedSettings {
+        toml::from_str(MANAGED).unwrap()
+    }
+
+    fn resolve(user: &str) -> EffectiveSettings {
+        managed().resolve(&parse_user_settings(user).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn managed_defaults_apply_to_keys_the_user_did_not_set() {
+        let effective = resolve("");
+        assert_eq!(effective.settings.polling.interval_secs, 300);
+        assert_eq!(effective.sources["polling.interval_secs"], SettingSource::ManagedDefault);
+        assert_eq!(effective.sources["thresholds.warning"], SettingSource::Default);
+    }
+
+    #[test]
+    fn user_values_override_managed_defaults() {
+        let effective = resolve("[polling]\ninterval_secs = 600");
+        assert_eq!(effective.settings.polling.interval_secs, 600);
+        assert_eq!(effective.sources["polling.interval_secs"], SettingSource::User);
+        assert_eq!(effective.settings.polling.profile_interval_secs, 3600);
+
+        // Even when the user's value is the built-in default
+        let effective = resolve("[polling]\nprofile_interval_secs = 1800");
+        assert_eq!(effective.settings.polling.profile_interval_secs, 1800);
+        assert_eq!(effective.sources["polling.profile_interval_secs"], SettingSource::User);
+    }
+
+    #[test]
+    fn locked_values_win() {
+        let effective = resolve("[api]\nbase_url = \"https://api.example.com\"\nproxy = \"http://proxy:3128\"");
+        assert_eq!(effective.settings.api.base_url, "https://claude-gateway.example.com");
+        // Locked without a managed value: the built-in default
+        assert_eq!(effective.settings.api.proxy, None);
+        assert_eq!(effective.sources["api.base_url"], SettingSource::Managed);
+
+        let effective = resolve("[polling]\ninterval_secs = 60");
+        assert_eq!(effective.settings.polling.interval_secs, 120);
+        assert_eq!(effective.sources["polling.interval_secs"], SettingSource::Managed);
+    }
+
+    #[test]
+    fn user_changes_only_record_what_changed() {
+        let managed = managed();
+        let user = parse_user_settings("[thresholds]\nwarning = 60.0").unwrap();
+        let effective = managed.resolve(&user).unwrap().settings;
+
+        let mut edited = effective.clone();
+        edited.polling.profile_interval_secs = 1800;
+        let changed = managed.user_changes(&user, &effective, &edited).unwrap();
+        assert!(changed.keys.contains("polling.profile_interval_secs"));
+        assert!(changed.keys.contains("thresholds.warning"));
+        // Shown managed values aren't copied into the user's settings
+        assert!(!changed.keys.contains("polling.interval_secs"));
+        assert!(!changed.keys.contains("api.base_url"));
+        assert_eq!(managed.resolve(&changed).unwrap().settings.polling.profile_interval_secs, 1800);
+
+        let mut edited = effective.clone();
+        edited.api.base_url = "https://api.example.com".to_string();
+        assert!(managed.user_changes(&user, &effective, &edited).is_err());
+    }
+}
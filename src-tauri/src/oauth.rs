@@ -0,0 +1,492 @@
+// Standalone sign-in for machines without Claude Code: the OAuth
+// authorization code flow with PKCE, the way Claude Code signs in. The
+// browser is sent to the authorize page, which redirects back to a one-shot
+// listener on a loopback port; the code is exchanged for tokens, which are
+// stored in the app's own credential entry (CredentialSource::App).
+
+use crate::credentials::{CredentialSource, OAuthToken};
+use crate::secret::SecretString;
+use crate::settings::ApiSettings;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+// Claude Code's public client; tokens it issues work with the usage endpoint
+pub const CLIENT_ID: &str = "9d1c250a-e61b-44d9-88ed-5944d1962f5e";
+pub const TOKEN_URL: &str = "https://console.anthropic.com/v1/oauth/token";
+const AUTHORIZE_URL: &str = "https://claude.ai/oauth/authorize";
+const REVOKE_URL: &str = "https://console.anthropic.com/v1/oauth/revoke";
+pub const SCOPES: &str = "user:profile user:inference";
+
+// Long enough to sign in, short enough not to leave a listener around forever
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+// Browsers open connections they may never send on; drop those after this
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+const SIGNED_IN_PAGE: &str = "<html><body><p>Signed in to Claude Usage Monitor. You can close this tab.</p></body></html>";
+const FAILED_PAGE: &str = "<html><body><p>Sign-in failed. Check Claude Usage Monitor for details.</p></body></html>";
+
+// Where the flow talks to; tests point these at a local stand-in server
+#[derive(Debug, Clone)]
+pub struct OAuthEndpoints {
+    pub authorize_url: String,
+    pub token_url: String,
+    pub revoke_url: String,
+    pub client_id: String,
+}
+
+impl Default for OAuthEndpoints {
+    fn default() -> Self {
+        Self {
+            authorize_url: AUTHORIZE_URL.to_string(),
+            token_url: TOKEN_URL.to_string(),
+            revoke_url: REVOKE_URL.to_string(),
+            client_id: CLIENT_ID.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: SecretString,
+    refresh_token: Option<SecretString>,
+    expires_in: Option<i64>,
+    // Space-separated
+    scope: Option<String>,
+}
+
+// The plan isn't part of the token response; the profile has it
+#[derive(Debug, Deserialize)]
+struct ProfileResponse {
+    organization: Option<ProfileOrganization>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProfileOrganization {
+    // e.g. "claude_max"
+    organization_type: Option<String>,
+    rate_limit_tier: Option<String>,
+}
+
+fn random_string(len: usize) -> Result<String, String> {
+    let mut bytes = vec![0u8; len];
+    getrandom::getrandom(&mut bytes).map_err(|e| format!("Failed to generate random bytes: {}", e))?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+// S256 code challenge for a PKCE verifier
+fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+// Request target of an HTTP request, e.g. "/callback?code=..."
+async fn read_request_target(stream: &mut TcpStream) -> Option<String> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 16 * 1024 {
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.lines().next()?.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => Some(target.to_string()),
+        _ => None,
+    }
+}
+
+// What one request to the listener means for the sign-in: the code, an error
+// from the authorize page, or None to keep waiting. Requests that aren't the
+// redirect, e.g. for a favicon, get 404; a redirect carrying another state
+// isn't ours, so it is turned away without ending the sign-in
+async fn handle_request(mut stream: TcpStream, expected_state: &str) -> Option<Result<String, String>> {
+    let target = tokio::time::timeout(REQUEST_TIMEOUT, read_request_target(&mut stream))
+        .await
+        .ok()
+        .flatten()?;
+    let url = reqwest::Url::parse(&format!("http://localhost{}", target)).ok()?;
+    if url.path() != "/callback" {
+        respond(&mut stream, "404 Not Found", "").await;
+        return None;
+    }
+
+    let param = |name: &str| url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.to_string());
+    if param("state").as_deref() != Some(expected_state) {
+        respond(&mut stream, "400 Bad Request", FAILED_PAGE).await;
+        return None;
+    }
+    if let Some(error) = param("error") {
+        respond(&mut stream, "200 OK", FAILED_PAGE).await;
+        let description = param("error_description").unwrap_or_default();
+        return Some(Err(format!("Sign-in was refused: {} {}", error, description).trim_end().to_string()));
+    }
+    let Some(code) = param("code") else {
+        respond(&mut stream, "400 Bad Request", FAILED_PAGE).await;
+        return Some(Err("Sign-in response had no authorization code".to_string()));
+    };
+    respond(&mut stream, "200 OK", SIGNED_IN_PAGE).await;
+    Some(Ok(code))
+}
+
+// Serves the loopback redirect until the authorize page sends the browser
+// back with a code (or an error). Each connection gets its own task, so one
+// that never sends a request doesn't hold up the rest
+async fn wait_for_code(listener: &TcpListener, expected_state: &str) -> Result<String, String> {
+    let (tx, mut outcomes) = mpsc::channel(1);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted.map_err(|e| format!("Sign-in listener failed: {}", e))?;
+                let (tx, expected_state) = (tx.clone(), expected_state.to_string());
+                tokio::spawn(async move {
+                    if let Some(outcome) = handle_request(stream, &expected_state).await {
+                        let _ = tx.send(outcome).await;
+                    }
+                });
+            }
+            Some(outcome) = outcomes.recv() => return outcome,
+        }
+    }
+}
+
+async fn exchange_code(
+    api: &ApiSettings,
+    endpoints: &OAuthEndpoints,
+    code: &str,
+    state: &str,
+    verifier: &str,
+    redirect_uri: &str,
+) -> Result<OAuthToken, String> {
+    let client = api.client()?;
+    let params = [
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("state", state),
+        ("redirect_uri", redirect_uri),
+        ("client_id", endpoints.client_id.as_str()),
+        ("code_verifier", verifier),
+    ];
+    let response = client
+        .post(&endpoints.token_url)
+        .form(&params)
+        .send()
+        .await
+        .map_err(|e| format!("Token request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("Token exchange failed ({}): {}", status, body));
+    }
+
+    let token: TokenResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse token response: {}", e))?;
+
+    let expires_at = token
+        .expires_in
+        .map(|secs| chrono::Utc::now().timestamp_millis() + secs * 1000);
+    Ok(OAuthToken {
+        access_token: token.access_token,
+        refresh_token: token.refresh_token,
+        expires_at,
+        scopes: token.scope.map(|scope| scope.split_whitespace().map(str::to_string).collect()),
+        subscription_type: None,
+        rate_limit_tier: None,
+    })
+}
+
+// Fills in the subscription fields Claude Code would have stored, so tiers
+// show up the same for both kinds of login. Best effort
+async fn add_subscription(api: &ApiSettings, token: &mut OAuthToken) {
+    let Ok(client) = api.client() else {
+        return;
+    };
+    let response = client
+        .get(api.url("/api/oauth/profile"))
+        .header("Authorization", format!("Bearer {}", token.access_token.expose()))
+        .header("anthropic-beta", "oauth-2025-04-20")
+        .send()
+        .await;
+    let Ok(profile) = (match response {
+        Ok(response) if response.status().is_success() => response.json::<ProfileResponse>().await,
+        _ => return,
+    }) else {
+        return;
+    };
+    if let Some(organization) = profile.organization {
+        token.subscription_type = organization
+            .organization_type
+            .map(|kind| kind.strip_prefix("claude_").unwrap_or(&kind).to_string());
+        token.rate_limit_tier = organization.rate_limit_tier;
+    }
+}
+
+// Runs the whole sign-in: open_browser is handed the authorize URL, and the
+// tokens end up in source
+pub async fn login(
+    api: &ApiSettings,
+    endpoints: &OAuthEndpoints,
+    source: &CredentialSource,
+    open_browser: impl FnOnce(&str) -> Result<(), String>,
+) -> Result<OAuthToken, String> {
+    let verifier = random_string(32)?;
+    let state = random_string(16)?;
+
+    let listener = TcpListener::bind(("127.0.0.1", 0))
+        .await
+        .map_err(|e| format!("Failed to start sign-in listener: {}", e))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to start sign-in listener: {}", e))?
+        .port();
+    let redirect_uri = format!("http://localhost:{}/callback", port);
+
+    let mut url = reqwest::Url::parse(&endpoints.authorize_url)
+        .map_err(|e| format!("Invalid authorize URL '{}': {}", endpoints.authorize_url, e))?;
+    url.query_pairs_mut()
+        .append_pair("code", "true")
+        .append_pair("client_id", &endpoints.client_id)
+        .append_pair("response_type", "code")
+        .append_pair("redirect_uri", &redirect_uri)
+        .append_pair("scope", SCOPES)
+        .append_pair("code_challenge", &code_challenge(&verifier))
+        .append_pair("code_challenge_method", "S256")
+        .append_pair("state", &state);
+    open_browser(url.as_str())?;
+
+    let code = tokio::time::timeout(LOGIN_TIMEOUT, wait_for_code(&listener, &state))
+        .await
+        .map_err(|_| "Timed out waiting for sign-in in the browser".to_string())??;
+    drop(listener);
+
+    let mut token = exchange_code(api, endpoints, &code, &state, &verifier, &redirect_uri).await?;
+    add_subscription(api, &mut token).await;
+    source.save_token(&token).await?;
+    Ok(token)
+}
+
+async fn revoke(api: &ApiSettings, endpoints: &OAuthEndpoints, token: &OAuthToken) -> Result<(), String> {
+    // Revoking the refresh token ends the whole grant
+    let (value, hint) = match token.refresh_token {
+        Some(ref refresh_token) => (refresh_token.expose(), "refresh_token"),
+        None => (token.access_token.expose(), "access_token"),
+    };
+    let params = [("token", value), ("token_type_hint", hint), ("client_id", endpoints.client_id.as_str())];
+    let response = api
+        .client()?
+        .post(&endpoints.revoke_url)
+        .form(&params)
+        .send()
+        .await
+        .map_err(|e| format!("Revoke request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Revoking the token failed ({})", response.status()));
+    }
+    Ok(())
+}
+
+// Revokes the tokens and removes them. They are removed even when revoking
+// fails, e.g. offline; the error is still returned so it can be shown
+pub async fn logout(api: &ApiSettings, endpoints: &OAuthEndpoints, source: &CredentialSource) -> Result<(), String> {
+    let revoked = match source.load_token_opt().await? {
+        Some(token) => revoke(api, endpoints, &token).await,
+        None => Ok(()),
+    };
+    source.remove().await?;
+    revoked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    // What the stand-in server has seen
+    #[derive(Default)]
+    struct Seen {
+        challenge: Option<String>,
+        token_requests: Vec<HashMap<String, String>>,
+        revoked: Vec<String>,
+    }
+
+    fn form(body: &str) -> HashMap<String, String> {
+        reqwest::Url::parse(&format!("http://x/?{}", body))
+            .unwrap()
+            .query_pairs()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    async fn read_request(stream: &mut TcpStream) -> (String, String) {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                    .unwrap_or(0);
+                if body.len() >= length || n == 0 {
+                    return (head.lines().next().unwrap().to_string(), body.to_string());
+                }
+            }
+        }
+    }
+
+    // Minimal token, revoke and profile endpoints that check PKCE
+    async fn stand_in_server(seen: Arc<Mutex<Seen>>) -> String {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let base = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let (request_line, body) = read_request(&mut stream).await;
+                let (status, response) = if request_line.starts_with("POST /token") {
+                    let params = form(&body);
+                    let mut seen = seen.lock().unwrap();
+                    let verified = params.get("code_verifier").map(|v| code_challenge(v)) == seen.challenge;
+                    seen.token_requests.push(params.clone());
+                    if verified && params.get("code").map(String::as_str) == Some("test-code") {
+                        ("200 OK", r#"{"access_token":"access-1","refresh_token":"refresh-1","expires_in":3600,"scope":"user:profile user:inference"}"#)
+                    } else {
+                        ("400 Bad Request", r#"{"error":"invalid_grant"}"#)
+                    }
+                } else if request_line.starts_with("POST /revoke") {
+                    seen.lock().unwrap().revoked.push(form(&body)["token"].clone());
+                    ("200 OK", "{}")
+                } else if request_line.starts_with("GET /api/oauth/profile") {
+                    ("200 OK", r#"{"account":{"email":"me@example.com"},"organization":{"organization_type":"claude_max","rate_limit_tier":"default_claude_max_20x"}}"#)
+                } else {
+                    ("404 Not Found", "")
+                };
+                let _ = stream
+                    .write_all(
+                        format!("HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, response.len(), response)
+                            .as_bytes(),
+                    )
+                    .await;
+            }
+        });
+        base
+    }
+
+    fn setup(name: &str, base: &str) -> (ApiSettings, OAuthEndpoints, CredentialSource) {
+        let dir = std::env::temp_dir().join(format!("claude-usage-monitor-oauth-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let api = ApiSettings { base_url: base.to_string(), proxy: None };
+        let endpoints = OAuthEndpoints {
+            authorize_url: format!("{}/authorize", base),
+            token_url: format!("{}/token", base),
+            revoke_url: format!("{}/revoke", base),
+            client_id: "test-client".to_string(),
+        };
+        let source = CredentialSource::File { path: dir.join("credentials.json").to_string_lossy().to_string() };
+        (api, endpoints, source)
+    }
+
+    // Plays the browser: follows the authorize URL straight back to the
+    // redirect URI, as the authorize page would after the user approves
+    fn browser(seen: Arc<Mutex<Seen>>, code: &'static str) -> impl FnOnce(&str) -> Result<(), String> {
+        move |url| {
+            let url = reqwest::Url::parse(url).unwrap();
+            let params: HashMap<String, String> = url.query_pairs().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            assert_eq!(params["client_id"], "test-client");
+            assert_eq!(params["code_challenge_method"], "S256");
+            seen.lock().unwrap().challenge = Some(params["code_challenge"].clone());
+            let redirect = format!("{}?code={}&state={}", params["redirect_uri"], code, params["state"]);
+            tokio::spawn(async move {
+                // A stray request first, which the listener should shrug off
+                let favicon = redirect.replace("/callback", "/favicon.ico").split('?').next().unwrap().to_string();
+                let _ = reqwest::get(favicon).await;
+                reqwest::get(redirect).await.unwrap();
+            });
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn login_and_logout() {
+        let seen = Arc::new(Mutex::new(Seen::default()));
+        let base = stand_in_server(seen.clone()).await;
+        let (api, endpoints, source) = setup("login", &base);
+
+        let token = login(&api, &endpoints, &source, browser(seen.clone(), "test-code")).await.unwrap();
+        assert_eq!(token.access_token.expose(), "access-1");
+        assert_eq!(token.subscription_type.as_deref(), Some("max"));
+
+        let stored = source.read_token().unwrap();
+        assert_eq!(stored.refresh_token.as_ref().map(SecretString::expose), Some("refresh-1"));
+        assert_eq!(stored.scopes, Some(vec!["user:profile".to_string(), "user:inference".to_string()]));
+        assert_eq!(stored.rate_limit_tier.as_deref(), Some("default_claude_max_20x"));
+        assert_eq!(seen.lock().unwrap().token_requests[0]["grant_type"], "authorization_code");
+
+        logout(&api, &endpoints, &source).await.unwrap();
+        assert_eq!(seen.lock().unwrap().revoked, vec!["refresh-1".to_string()]);
+        assert!(source.read_token_opt().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn stray_connections_do_not_end_the_login() {
+        let seen = Arc::new(Mutex::new(Seen::default()));
+        let base = stand_in_server(seen.clone()).await;
+        let (api, endpoints, source) = setup("stray", &base);
+
+        let follow_redirect = browser(seen, "test-code");
+        let open_browser = move |url: &str| {
+            let params: HashMap<String, String> =
+                reqwest::Url::parse(url).unwrap().query_pairs().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            let callback = reqwest::Url::parse(&params["redirect_uri"]).unwrap();
+            let port = callback.port().unwrap();
+            let wrong_state = format!("{}?code=test-code&state=someone-else", callback);
+            let url = url.to_string();
+            tokio::spawn(async move {
+                // A preconnected socket that never sends anything, then a
+                // redirect meant for another sign-in
+                let _idle = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+                let response = reqwest::get(wrong_state).await.unwrap();
+                assert_eq!(response.status(), 400);
+                follow_redirect(&url).unwrap();
+                tokio::time::sleep(Duration::from_secs(30)).await;
+            });
+            Ok(())
+        };
+
+        let token = login(&api, &endpoints, &source, open_browser).await.unwrap();
+        assert_eq!(token.access_token.expose(), "access-1");
+    }
+
+    #[tokio::test]
+    async fn rejected_code_stores_nothing() {
+        let seen = Arc::new(Mutex::new(Seen::default()));
+        let base = stand_in_server(seen.clone()).await;
+        let (api, endpoints, source) = setup("rejected", &base);
+
+        let result = login(&api, &endpoints, &source, browser(seen, "wrong-code")).await;
+        assert!(result.unwrap_err().contains("Token exchange failed"));
+        assert!(source.read_token_opt().unwrap().is_none());
+    }
+}
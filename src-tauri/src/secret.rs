@@ -0,0 +1,65 @@
+// Tokens and other secrets. Debug and Display print a placeholder, there is
+// deliberately no Serialize, and the memory is wiped on drop; the value is
+// only reachable through expose().
+
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use zeroize::Zeroize;
+
+#[derive(Clone)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(value: String) -> Self {
+        Self(value)
+    }
+
+    // Every use of the plain value goes through here, so it is easy to audit
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretString(<redacted>)")
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<redacted>")
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_printed() {
+        let secret: SecretString = serde_json::from_str(r#""sk-ant-oat01-secret""#).unwrap();
+        assert_eq!(secret.expose(), "sk-ant-oat01-secret");
+        assert_eq!(format!("{:?}", secret), "SecretString(<redacted>)");
+        assert_eq!(secret.to_string(), "<redacted>");
+        assert!(!format!("{:?}", Some(secret)).contains("sk-ant"));
+    }
+}
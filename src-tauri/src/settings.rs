@@ -0,0 +1,513 @@
+// Versioned settings file under the platform config directory
+// (~/Library/Application Support on macOS, $XDG_CONFIG_HOME on Linux).
+//
+// Older installs kept the Chrome profile in ~/.claude-usage-monitor-profile
+// and everything else in ~/.claude-usage-monitor.json; both are migrated the
+// first time the settings file is missing.
+
+use crate::accounts::{AccountSettings, TrayAccount};
+use crate::autostart::AutostartSettings;
+use crate::hotkeys::{self, HotkeySettings};
+use crate::tray_format::{Template, DEFAULT_TRAY_FORMAT};
+use crate::tray_icon::TrayIconSettings;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+pub const SETTINGS_VERSION: u32 = 1;
+pub const MIN_POLL_INTERVAL_SECS: u64 = 30;
+pub const DEFAULT_API_BASE_URL: &str = "https://api.anthropic.com";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct PollingSettings {
+    pub interval_secs: u64,
+    // Profile and subscription change rarely, so they are re-read less often;
+    // credential changes re-read them right away
+    pub profile_interval_secs: u64,
+}
+
+impl Default for PollingSettings {
+    fn default() -> Self {
+        Self { interval_secs: 60, profile_interval_secs: 30 * 60 }
+    }
+}
+
+// Utilization percentages at which bars and icons turn yellow and red
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ThresholdSettings {
+    pub warning: f64,
+    pub critical: f64,
+}
+
+impl Default for ThresholdSettings {
+    fn default() -> Self {
+        Self { warning: 50.0, critical: 80.0 }
+    }
+}
+
+// Mapping browser for an account that uses browser and profile below, kept
+// so the account isn't matched to a profile by email again
+pub const DEFAULT_MAPPING: &str = "default";
+
+// Where links go while a given Claude account is active
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ProfileMapping {
+    // Claude account email, matched case-insensitively
+    pub email: String,
+    pub browser: String,
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct BrowserSettings {
+    // Browser id, e.g. "chrome", "brave" or "firefox"; Chrome when unset
+    pub browser: Option<String>,
+    // Profile within that browser: a Chromium profile directory such as
+    // "Default" or "Profile 2", or a Firefox profile path
+    pub profile: Option<String>,
+    // Hosts open_url may open, matched exactly. Only settings.toml and
+    // managed settings can change these, never the webview
+    pub allowed_hosts: Vec<String>,
+    // Per-account choices; browser and profile above apply to accounts
+    // without one
+    pub profiles: Vec<ProfileMapping>,
+}
+
+impl Default for BrowserSettings {
+    fn default() -> Self {
+        Self {
+            browser: None,
+            profile: None,
+            allowed_hosts: vec!["claude.ai".to_string(), "console.anthropic.com".to_string()],
+            profiles: Vec::new(),
+        }
+    }
+}
+
+impl BrowserSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        for host in &self.allowed_hosts {
+            let valid = reqwest::Url::parse(&format!("https://{}/", host))
+                .is_ok_and(|url| url.host_str() == Some(host.as_str()));
+            if !valid {
+                return Err(format!("'{}' in allowed_hosts is not a host name", host));
+            }
+        }
+        for (i, mapping) in self.profiles.iter().enumerate() {
+            if mapping.email.is_empty() || mapping.browser.is_empty() {
+                return Err("profiles entries need an email and a browser".to_string());
+            }
+            if self.profiles[..i].iter().any(|other| other.email.eq_ignore_ascii_case(&mapping.email)) {
+                return Err(format!("'{}' is mapped more than once in profiles", mapping.email));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn mapping(&self, email: &str) -> Option<&ProfileMapping> {
+        self.profiles.iter().find(|mapping| mapping.email.eq_ignore_ascii_case(email))
+    }
+
+    // Browser id and profile to open links with while the account with this
+    // email is active
+    pub fn profile_for(&self, email: Option<&str>) -> (String, Option<String>) {
+        match email.and_then(|email| self.mapping(email)) {
+            Some(mapping) if mapping.browser != DEFAULT_MAPPING => (mapping.browser.clone(), mapping.profile.clone()),
+            _ => (
+                self.browser.clone().unwrap_or_else(|| crate::browser::DEFAULT_BROWSER.to_string()),
+                self.profile.clone(),
+            ),
+        }
+    }
+
+    // Maps email to a profile, or to the default one when browser is None
+    pub fn set_mapping(&mut self, email: &str, browser: Option<(String, Option<String>)>) {
+        self.profiles.retain(|mapping| !mapping.email.eq_ignore_ascii_case(email));
+        let (browser, profile) = browser.unwrap_or_else(|| (DEFAULT_MAPPING.to_string(), None));
+        self.profiles.push(ProfileMapping { email: email.to_string(), browser, profile });
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ApiSettings {
+    // Usage and profile endpoints are requested relative to this, so a
+    // gateway in front of the API can be used
+    pub base_url: String,
+    // e.g. "http://proxy.example.com:3128"; HTTPS_PROXY and friends apply when unset
+    pub proxy: Option<String>,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        Self {
+            base_url: DEFAULT_API_BASE_URL.to_string(),
+            proxy: None,
+        }
+    }
+}
+
+impl ApiSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let url = reqwest::Url::parse(&self.base_url)
+            .map_err(|e| format!("Invalid base_url '{}': {}", self.base_url, e))?;
+        if url.scheme() != "https" {
+            return Err(format!("base_url '{}' must use https", self.base_url));
+        }
+        if let Some(ref proxy) = self.proxy {
+            reqwest::Proxy::all(proxy).map_err(|e| format!("Invalid proxy '{}': {}", proxy, e))?;
+        }
+        Ok(())
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url.trim_end_matches('/'), path)
+    }
+
+    // HTTP client for anything the app requests, going through the configured proxy
+    pub fn client(&self) -> Result<reqwest::Client, String> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(ref proxy) = self.proxy {
+            let proxy = reqwest::Proxy::all(proxy).map_err(|e| format!("Invalid proxy '{}': {}", proxy, e))?;
+            builder = builder.proxy(proxy);
+        }
+        builder.build().map_err(|e| format!("Failed to create HTTP client: {}", e))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct TraySettings {
+    pub format: String,
+    pub icon: TrayIconSettings,
+    pub account: TrayAccount,
+}
+
+impl Default for TraySettings {
+    fn default() -> Self {
+        Self {
+            format: DEFAULT_TRAY_FORMAT.to_string(),
+            icon: TrayIconSettings::default(),
+            account: TrayAccount::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    // Account id that commands act on by default; the first account when unset
+    pub active_account: Option<String>,
+    pub api: ApiSettings,
+    pub polling: PollingSettings,
+    pub thresholds: ThresholdSettings,
+    pub browser: BrowserSettings,
+    pub tray: TraySettings,
+    pub hotkeys: HotkeySettings,
+    pub autostart: AutostartSettings,
+    // Empty means a single account using Claude Code's login
+    pub accounts: Vec<AccountSettings>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            active_account: None,
+            api: ApiSettings::default(),
+            polling: PollingSettings::default(),
+            thresholds: ThresholdSettings::default(),
+            browser: BrowserSettings::default(),
+            tray: TraySettings::default(),
+            hotkeys: HotkeySettings::default(),
+            autostart: AutostartSettings::default(),
+            accounts: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    // Settings the webview may not change through update_settings: they guard
+    // what the app does on the webview's behalf, so changing them is left to
+    // settings.toml and managed settings
+    pub fn check_webview_edit(&self, edited: &Settings) -> Result<(), String> {
+        if edited.browser.allowed_hosts != self.browser.allowed_hosts {
+            return Err("browser.allowed_hosts can only be changed in settings.toml".to_string());
+        }
+        Ok(())
+    }
+
+    // Keeps the settings check_webview_edit protects as they are in self
+    pub fn keep_file_only(&self, edited: &mut Settings) {
+        edited.browser.allowed_hosts = self.browser.allowed_hosts.clone();
+    }
+
+    // Reports every problem at once so a hand-edited file can be fixed in one go
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+
+        if self.version > SETTINGS_VERSION {
+            errors.push(format!(
+                "version {} was written by a newer release (this one supports {})",
+                self.version, SETTINGS_VERSION
+            ));
+        }
+        if let Err(e) = self.api.validate() {
+            errors.push(format!("api: {}", e));
+        }
+        if self.polling.interval_secs < MIN_POLL_INTERVAL_SECS {
+            errors.push(format!(
+                "polling.interval_secs must be at least {} (got {})",
+                MIN_POLL_INTERVAL_SECS, self.polling.interval_secs
+            ));
+        }
+        if self.polling.profile_interval_secs < MIN_POLL_INTERVAL_SECS {
+            errors.push(format!(
+                "polling.profile_interval_secs must be at least {} (got {})",
+                MIN_POLL_INTERVAL_SECS, self.polling.profile_interval_secs
+            ));
+        }
+
+        let thresholds = &self.thresholds;
+        for (name, value) in [("warning", thresholds.warning), ("critical", thresholds.critical)] {
+            if !(0.0..=100.0).contains(&value) {
+                errors.push(format!("thresholds.{} must be between 0 and 100 (got {})", name, value));
+            }
+        }
+        if thresholds.warning >= thresholds.critical {
+            errors.push("thresholds.warning must be lower than thresholds.critical".to_string());
+        }
+
+        if let Err(e) = self.browser.validate() {
+            errors.push(format!("browser: {}", e));
+        }
+        if let Err(e) = crate::accounts::validate_accounts(&self.accounts) {
+            errors.push(format!("accounts: {}", e));
+        }
+        if let Err(e) = Template::parse(&self.tray.format) {
+            errors.push(format!("tray.format: {}", e));
+        }
+        if let Err(e) = hotkeys::validate_hotkeys(&self.hotkeys) {
+            errors.push(format!("hotkeys: {}", e));
+        }
+        if let Err(e) = self.autostart.validate() {
+            errors.push(format!("autostart: {}", e));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Invalid settings: {}", errors.join("; ")))
+        }
+    }
+}
+
+// The user's settings: a value for every key, and which keys settings.toml
+// actually sets. Only those are written back, and only those override
+// managed defaults, even where they equal the built-in default
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserSettings {
+    pub settings: Settings,
+    pub keys: BTreeSet<String>,
+}
+
+pub fn to_table(settings: &Settings) -> Result<toml::Table, String> {
+    toml::Table::try_from(settings).map_err(|e| format!("Failed to serialize settings: {}", e))
+}
+
+// Flattens nested tables into dotted keys. Arrays are leaves, so a list like
+// browser.allowed_hosts is set or managed as a whole.
+pub fn flatten(table: &toml::Table, prefix: &str, out: &mut BTreeMap<String, toml::Value>) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match value {
+            toml::Value::Table(inner) => flatten(inner, &path, out),
+            _ => {
+                out.insert(path, value.clone());
+            }
+        }
+    }
+}
+
+pub fn unflatten(leaves: BTreeMap<String, toml::Value>) -> toml::Table {
+    let mut root = toml::Table::new();
+    for (path, value) in leaves {
+        let mut parts: Vec<&str> = path.split('.').collect();
+        let leaf = parts.pop().expect("split always yields at least one part");
+        let mut table = &mut root;
+        for part in parts {
+            table = table
+                .entry(part.to_string())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .expect("settings keys are never both a value and a table");
+        }
+        table.insert(leaf.to_string(), value);
+    }
+    root
+}
+
+fn home_dir() -> PathBuf {
+    PathBuf::from(std::env::var("HOME").unwrap_or_default())
+}
+
+pub fn settings_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| home_dir().join(".config"))
+        .join("claude-usage-monitor")
+        .join("settings.toml")
+}
+
+fn legacy_profile_path() -> PathBuf {
+    home_dir().join(".claude-usage-monitor-profile")
+}
+
+// The browser profile chosen before settings.toml existed, which was all
+// the app stored
+fn migrate_legacy() -> Option<UserSettings> {
+    migrate(&std::fs::read_to_string(legacy_profile_path()).ok()?)
+}
+
+fn migrate(legacy_profile: &str) -> Option<UserSettings> {
+    let profile = legacy_profile.trim();
+    if profile.is_empty() {
+        return None;
+    }
+    let mut settings = Settings::default();
+    settings.browser.profile = Some(profile.to_string());
+    Some(UserSettings { settings, keys: BTreeSet::from(["browser.profile".to_string()]) })
+}
+
+pub fn parse_settings(content: &str) -> Result<Settings, String> {
+    let settings: Settings =
+        toml::from_str(content).map_err(|e| format!("Failed to parse settings: {}", e))?;
+    settings.validate()?;
+    Ok(settings)
+}
+
+pub fn parse_user_settings(content: &str) -> Result<UserSettings, String> {
+    let settings = parse_settings(content)?;
+    let table: toml::Table = toml::from_str(content).map_err(|e| format!("Failed to parse settings: {}", e))?;
+    let mut leaves = BTreeMap::new();
+    flatten(&table, "", &mut leaves);
+    Ok(UserSettings { settings, keys: leaves.into_keys().collect() })
+}
+
+// A missing file is not an error: the legacy profile is migrated if
+// present, otherwise defaults are used
+pub fn load_settings() -> Result<UserSettings, String> {
+    let path = settings_path();
+    match std::fs::read_to_string(&path) {
+        Ok(content) => parse_user_settings(&content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => match migrate_legacy() {
+            Some(user) => {
+                save_settings(&user)?;
+                Ok(user)
+            }
+            None => Ok(UserSettings::default()),
+        },
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+// Writes the keys the user set, plus the version
+pub fn save_settings(user: &UserSettings) -> Result<(), String> {
+    let path = settings_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    let mut leaves = BTreeMap::new();
+    flatten(&to_table(&user.settings)?, "", &mut leaves);
+    leaves.retain(|key, _| key == "version" || user.keys.contains(key));
+    let content = toml::to_string_pretty(&unflatten(leaves))
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+
+    // Write to a sibling file and rename so readers never see a partial file
+    let tmp_path = path.with_extension("toml.tmp");
+    std::fs::write(&tmp_path, content).map_err(|e| format!("Failed to write settings: {}", e))?;
+    std::fs::rename(&tmp_path, &path).map_err(|e| format!("Failed to write settings: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_the_legacy_profile() {
+        let user = migrate("Profile 2\n").unwrap();
+        assert_eq!(user.settings.browser.profile.as_deref(), Some("Profile 2"));
+        assert_eq!(user.settings.version, SETTINGS_VERSION);
+        assert_eq!(user.keys, BTreeSet::from(["browser.profile".to_string()]));
+        assert!(user.settings.validate().is_ok());
+
+        assert_eq!(migrate("  "), None);
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let rejected = [
+            "version = 99",
+            "[api]\nbase_url = \"http://api.example.com\"",
+            "[api]\nproxy = \"not a proxy\"",
+            "[polling]\ninterval_secs = 1",
+            "[thresholds]\nwarning = 95.0\ncritical = 90.0",
+            "[thresholds]\ncritical = 120.0",
+            "[browser]\nallowed_hosts = [\"claude.ai/path\"]",
+            "[tray]\nformat = \"{five_hour\"",
+            "[[accounts]]\nid = \"\"\nname = \"Nameless\"",
+        ];
+        for content in rejected {
+            assert!(parse_settings(content).is_err(), "{}", content);
+        }
+        assert!(parse_settings("not = [toml").is_err());
+        assert_eq!(parse_settings(""), Ok(Settings::default()));
+
+        let (supported, unsupported) =
+            if cfg!(target_os = "macos") { ("launch_agent", "systemd") } else { ("systemd", "launch_agent") };
+        assert!(parse_settings(&format!("[autostart]\nmethod = \"{}\"", supported)).is_ok());
+        let e = parse_settings(&format!("[autostart]\nmethod = \"{}\"", unsupported)).unwrap_err();
+        assert!(e.contains(&format!("autostart: method '{}'", unsupported)), "{}", e);
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let e = parse_settings("[polling]\ninterval_secs = 1\n[tray]\nformat = \"{nope}\"").unwrap_err();
+        assert!(e.contains("polling.interval_secs"), "{}", e);
+        assert!(e.contains("tray.format"), "{}", e);
+    }
+
+    #[test]
+    fn webview_cannot_change_file_only_settings() {
+        let current = Settings::default();
+        let mut edited = current.clone();
+        edited.polling.interval_secs = 600;
+        assert!(current.check_webview_edit(&edited).is_ok());
+
+        edited.browser.allowed_hosts.push("example.com".to_string());
+        assert!(current.check_webview_edit(&edited).is_err());
+
+        current.keep_file_only(&mut edited);
+        assert_eq!(edited.browser.allowed_hosts, current.browser.allowed_hosts);
+        assert_eq!(edited.polling.interval_secs, 600);
+    }
+
+    #[test]
+    fn cleared_mappings_use_the_default_profile() {
+        let mut browser = BrowserSettings { browser: Some("brave".to_string()), ..Default::default() };
+        browser.set_mapping("Me@example.com", Some(("firefox".to_string(), Some("abcd".to_string()))));
+        assert_eq!(browser.profile_for(Some("me@example.com")), ("firefox".to_string(), Some("abcd".to_string())));
+
+        browser.set_mapping("me@example.com", None);
+        assert_eq!(browser.profile_for(Some("me@example.com")), ("brave".to_string(), None));
+        // Still mapped, so it isn't matched by email again
+        assert!(browser.mapping("me@example.com").is_some());
+        assert_eq!(browser.profiles.len(), 1);
+        assert!(browser.validate().is_ok());
+    }
+}
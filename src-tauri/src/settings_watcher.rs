@@ -0,0 +1,87 @@
+// Applies edits to settings.toml made outside the app (dotfile managers,
+// editors) without a restart. Invalid edits are reported with a
+// `settings-error` event and the last good settings stay in effect; the app
+// won't write the file until it parses again.
+// Managed settings are layered on top as for any other change.
+
+use crate::settings::{self, UserSettings};
+use crate::AppState;
+use notify::{RecursiveMode, Watcher};
+use std::sync::mpsc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+// Editors often write a file in several steps; wait for them to settle
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+async fn reload(app: &AppHandle) {
+    let path = settings::settings_path();
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        // Deleted or mid-rename: keep what we have
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+        Err(e) => {
+            let e = format!("Failed to read {}: {}", path.display(), e);
+            *app.state::<AppState>().settings_error.lock().await = Some(e.clone());
+            let _ = app.emit("settings-error", e);
+            return;
+        }
+    };
+
+    let new_settings: UserSettings = match settings::parse_user_settings(&content) {
+        Ok(settings) => settings,
+        Err(e) => {
+            *app.state::<AppState>().settings_error.lock().await = Some(e.clone());
+            let _ = app.emit("settings-error", e);
+            return;
+        }
+    };
+    *app.state::<AppState>().settings_error.lock().await = None;
+
+    // Also filters out the watcher seeing the app's own writes
+    let state = app.state::<AppState>();
+    let mut user = state.user_settings.lock().await;
+    if new_settings == *user {
+        return;
+    }
+
+    // Locked values edited into the file are simply overridden again
+    if let Err(e) = crate::store_user_settings(app, &mut user, new_settings).await {
+        let _ = app.emit("settings-error", e);
+    }
+}
+
+// Watches the settings directory rather than the file so that editors and
+// tools which replace the file by renaming over it are still picked up
+pub fn watch_settings(app: AppHandle) -> Result<(), String> {
+    let path = settings::settings_path();
+    let dir = path
+        .parent()
+        .ok_or_else(|| "Settings path has no parent directory".to_string())?
+        .to_path_buf();
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).map_err(|e| format!("Failed to create settings watcher: {}", e))?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("Failed to watch {}: {}", dir.display(), e))?;
+
+    std::thread::spawn(move || {
+        // The watcher stops when dropped, so it lives as long as this thread
+        let _watcher = watcher;
+        let touches_settings = |event: &notify::Result<notify::Event>| {
+            event.as_ref().is_ok_and(|event| event.paths.iter().any(|p| p == &path))
+        };
+
+        while let Ok(event) = rx.recv() {
+            if !touches_settings(&event) {
+                continue;
+            }
+            while rx.recv_timeout(DEBOUNCE).is_ok() {}
+            tauri::async_runtime::block_on(reload(&app));
+        }
+    });
+    Ok(())
+}
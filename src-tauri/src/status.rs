@@ -0,0 +1,195 @@
+// How current an account's usage is, and if it isn't, why: one state per
+// account with when usage was last fetched and last tried, and when the next
+// poll is due. Sent as `status-changed` and by get_status, so the popup and
+// tray can say e.g. "Offline, data is 14 min old".
+
+use crate::accounts::AccountState;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::Duration;
+
+// Usage older than this many poll intervals is stale
+const STALE_INTERVALS: u32 = 3;
+// Longest the poller waits between attempts while fetches keep failing
+const MAX_BACKOFF: Duration = Duration::from_secs(15 * 60);
+
+// Why a fetch failed, as far as the status is concerned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Failure {
+    // No response at all: no network, DNS, timeouts
+    Offline,
+    // Rejected even after refreshing the token, or not logged in
+    AuthRequired,
+    RateLimited { retry_after: Option<Duration> },
+    Other,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MonitorState {
+    Ok,
+    // Not updated for a while, or the last fetch failed once
+    Stale,
+    Offline,
+    AuthRequired,
+    RateLimited,
+    // Fetches keep failing, so the poller waits longer between them
+    BackingOff,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct MonitorStatus {
+    pub account_id: String,
+    pub state: MonitorState,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_attempt: Option<DateTime<Utc>>,
+    pub next_poll: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+    // One line for the tray and popup, e.g. "Offline, data is 14 min old";
+    // None while usage is current
+    pub message: Option<String>,
+}
+
+impl MonitorStatus {
+    pub fn new(slot: &AccountState, interval: Duration, now: DateTime<Utc>) -> Self {
+        let stale_after = interval.saturating_mul(STALE_INTERVALS);
+        let fresh = slot
+            .last_updated
+            .is_some_and(|at| (now - at).to_std().map_or(true, |age| age <= stale_after));
+        let state = match slot.failure {
+            _ if !slot.logged_in => MonitorState::AuthRequired,
+            Some(Failure::AuthRequired) => MonitorState::AuthRequired,
+            Some(Failure::RateLimited { .. }) => MonitorState::RateLimited,
+            Some(Failure::Offline) => MonitorState::Offline,
+            Some(Failure::Other) if slot.failures > 1 => MonitorState::BackingOff,
+            Some(Failure::Other) => MonitorState::Stale,
+            None if !fresh => MonitorState::Stale,
+            None => MonitorState::Ok,
+        };
+        let mut status = Self {
+            account_id: slot.id.clone(),
+            state,
+            last_success: slot.last_updated,
+            last_attempt: slot.last_attempt,
+            next_poll: slot.next_poll,
+            error: slot.last_error.clone(),
+            message: None,
+        };
+        status.message = status.describe(now);
+        status
+    }
+
+    fn describe(&self, now: DateTime<Utc>) -> Option<String> {
+        let reason = match self.state {
+            MonitorState::Ok => return None,
+            MonitorState::Stale => None,
+            MonitorState::Offline => Some("Offline"),
+            MonitorState::AuthRequired => Some("Sign-in needed"),
+            MonitorState::RateLimited => Some("Rate limited"),
+            MonitorState::BackingOff => Some("Retrying less often"),
+        };
+        let age = self.last_success.map(|at| format_age(now - at));
+        Some(match (reason, age) {
+            (Some(reason), Some(age)) => format!("{}, data is {} old", reason, age),
+            (Some(reason), None) => format!("{}, no data yet", reason),
+            (None, Some(age)) => format!("Data is {} old", age),
+            (None, None) => "No usage data yet".to_string(),
+        })
+    }
+}
+
+// "14 min", "3 h", "2 days"
+fn format_age(age: chrono::Duration) -> String {
+    match age.num_minutes() {
+        ..=0 => "under a minute".to_string(),
+        minutes @ 1..=59 => format!("{} min", minutes),
+        minutes if minutes < 48 * 60 => format!("{} h", minutes / 60),
+        minutes => format!("{} days", minutes / (24 * 60)),
+    }
+}
+
+// How long the poller waits before the next attempt: the polling interval,
+// doubled for every failure in a row after the first, up to MAX_BACKOFF, and
+// no sooner than a rate limit's Retry-After
+pub fn next_delay(slot: &AccountState, interval: Duration) -> Duration {
+    let backoff = match slot.failures {
+        0 | 1 => interval,
+        n => interval
+            .saturating_mul(2u32.saturating_pow(n - 1))
+            .min(MAX_BACKOFF.max(interval)),
+    };
+    match slot.failure {
+        Some(Failure::RateLimited { retry_after: Some(after) }) => backoff.max(after),
+        _ => backoff,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::AccountSettings;
+
+    const INTERVAL: Duration = Duration::from_secs(60);
+
+    fn slot() -> AccountState {
+        AccountState::new(&AccountSettings::default())
+    }
+
+    fn failed(times: u32, failure: Failure) -> AccountState {
+        let mut slot = slot();
+        for _ in 0..times {
+            slot.record_failure("failed".to_string(), failure);
+        }
+        slot
+    }
+
+    #[test]
+    fn states() {
+        let now = Utc::now();
+        let state = |slot: &AccountState| MonitorStatus::new(slot, INTERVAL, now).state;
+
+        assert_eq!(state(&slot()), MonitorState::Stale);
+        let mut fresh = slot();
+        fresh.last_updated = Some(now - chrono::Duration::seconds(90));
+        assert_eq!(state(&fresh), MonitorState::Ok);
+        let mut old = slot();
+        old.last_updated = Some(now - chrono::Duration::minutes(14));
+        assert_eq!(state(&old), MonitorState::Stale);
+
+        assert_eq!(state(&failed(1, Failure::Other)), MonitorState::Stale);
+        assert_eq!(state(&failed(2, Failure::Other)), MonitorState::BackingOff);
+        assert_eq!(state(&failed(1, Failure::Offline)), MonitorState::Offline);
+        assert_eq!(state(&failed(1, Failure::AuthRequired)), MonitorState::AuthRequired);
+        assert_eq!(state(&failed(1, Failure::RateLimited { retry_after: None })), MonitorState::RateLimited);
+        let mut logged_out = slot();
+        logged_out.log_out();
+        assert_eq!(state(&logged_out), MonitorState::AuthRequired);
+    }
+
+    #[test]
+    fn describe() {
+        let now = Utc::now();
+        let mut slot = failed(1, Failure::Offline);
+        slot.last_updated = Some(now - chrono::Duration::minutes(14));
+        let status = MonitorStatus::new(&slot, INTERVAL, now);
+        assert_eq!(status.message.as_deref(), Some("Offline, data is 14 min old"));
+
+        slot.failure = None;
+        let status = MonitorStatus::new(&slot, INTERVAL, now);
+        assert_eq!(status.message.as_deref(), Some("Data is 14 min old"));
+
+        slot.last_updated = Some(now);
+        assert_eq!(MonitorStatus::new(&slot, INTERVAL, now).message, None);
+    }
+
+    #[test]
+    fn backoff() {
+        assert_eq!(next_delay(&slot(), INTERVAL), INTERVAL);
+        assert_eq!(next_delay(&failed(1, Failure::Other), INTERVAL), INTERVAL);
+        assert_eq!(next_delay(&failed(3, Failure::Offline), INTERVAL), INTERVAL * 4);
+        assert_eq!(next_delay(&failed(40, Failure::Other), INTERVAL), MAX_BACKOFF);
+
+        let retry_after = Some(Duration::from_secs(600));
+        assert_eq!(next_delay(&failed(1, Failure::RateLimited { retry_after }), INTERVAL), Duration::from_secs(600));
+    }
+}
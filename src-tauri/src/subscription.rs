@@ -0,0 +1,129 @@
+// The Claude plan an account is on, from the subscriptionType and
+// rateLimitTier fields Claude Code stores alongside the OAuth token.
+
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+// Serialized with its Display text as `label`, e.g.
+// {"tier":"max","multiplier":20,"label":"Max 20x"}, so the frontend shows
+// the same names as the tray
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(remote = "Self", tag = "tier", rename_all = "snake_case")]
+pub enum SubscriptionTier {
+    Free,
+    Pro,
+    // Usage multiple of Pro, e.g. 5 or 20; None when the rate limit tier
+    // doesn't say
+    Max { multiplier: Option<u32> },
+    Team,
+    Enterprise,
+    // Anything else, kept as stored so it can still be shown and reported
+    Unknown {
+        subscription_type: Option<String>,
+        rate_limit_tier: Option<String>,
+    },
+}
+
+// "default_claude_max_20x" -> 20
+fn multiplier(rate_limit_tier: &str) -> Option<u32> {
+    rate_limit_tier
+        .split(['_', '-'])
+        .find_map(|part| part.strip_suffix('x').and_then(|n| n.parse().ok()))
+}
+
+impl SubscriptionTier {
+    // No subscriptionType at all is a free account, as it always was
+    pub fn parse(subscription_type: Option<&str>, rate_limit_tier: Option<&str>) -> Self {
+        match subscription_type.map(str::to_ascii_lowercase).as_deref() {
+            Some("free") | None => SubscriptionTier::Free,
+            Some("pro") => SubscriptionTier::Pro,
+            Some("max") => SubscriptionTier::Max { multiplier: rate_limit_tier.and_then(multiplier) },
+            Some("team") => SubscriptionTier::Team,
+            Some("enterprise") => SubscriptionTier::Enterprise,
+            _ => SubscriptionTier::Unknown {
+                subscription_type: subscription_type.map(str::to_string),
+                rate_limit_tier: rate_limit_tier.map(str::to_string),
+            },
+        }
+    }
+}
+
+impl Serialize for SubscriptionTier {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Labelled<'a> {
+            #[serde(flatten, serialize_with = "tagged")]
+            tier: &'a SubscriptionTier,
+            label: String,
+        }
+        fn tagged<S: Serializer>(tier: &&SubscriptionTier, serializer: S) -> Result<S::Ok, S::Error> {
+            SubscriptionTier::serialize(tier, serializer)
+        }
+        Labelled { tier: self, label: self.to_string() }.serialize(serializer)
+    }
+}
+
+impl fmt::Display for SubscriptionTier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubscriptionTier::Free => write!(f, "Free"),
+            SubscriptionTier::Pro => write!(f, "Pro"),
+            SubscriptionTier::Max { multiplier: Some(multiplier) } => write!(f, "Max {}x", multiplier),
+            SubscriptionTier::Max { multiplier: None } => write!(f, "Max"),
+            SubscriptionTier::Team => write!(f, "Team"),
+            SubscriptionTier::Enterprise => write!(f, "Enterprise"),
+            SubscriptionTier::Unknown { subscription_type: Some(raw), .. } => write!(f, "{}", raw),
+            SubscriptionTier::Unknown { subscription_type: None, .. } => write!(f, "Unknown"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unknown(subscription_type: Option<&str>, rate_limit_tier: Option<&str>) -> SubscriptionTier {
+        SubscriptionTier::Unknown {
+            subscription_type: subscription_type.map(str::to_string),
+            rate_limit_tier: rate_limit_tier.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn parse_known_tiers() {
+        let cases = [
+            (Some("free"), None, SubscriptionTier::Free, "Free"),
+            (Some("pro"), Some("default_claude_ai"), SubscriptionTier::Pro, "Pro"),
+            (Some("max"), Some("default_claude_max_5x"), SubscriptionTier::Max { multiplier: Some(5) }, "Max 5x"),
+            (Some("max"), Some("default_claude_max_20x"), SubscriptionTier::Max { multiplier: Some(20) }, "Max 20x"),
+            (Some("Max"), Some("claude-max-20x"), SubscriptionTier::Max { multiplier: Some(20) }, "Max 20x"),
+            (Some("max"), Some("default_claude_ai"), SubscriptionTier::Max { multiplier: None }, "Max"),
+            (Some("max"), None, SubscriptionTier::Max { multiplier: None }, "Max"),
+            (Some("team"), Some("default_raven"), SubscriptionTier::Team, "Team"),
+            (Some("enterprise"), None, SubscriptionTier::Enterprise, "Enterprise"),
+            (Some("platinum"), Some("tier_x"), unknown(Some("platinum"), Some("tier_x")), "platinum"),
+            (None, Some("default_claude_ai"), SubscriptionTier::Free, "Free"),
+            (None, None, SubscriptionTier::Free, "Free"),
+        ];
+        for (subscription_type, rate_limit_tier, expected, label) in cases {
+            let tier = SubscriptionTier::parse(subscription_type, rate_limit_tier);
+            assert_eq!(tier, expected, "{:?} / {:?}", subscription_type, rate_limit_tier);
+            assert_eq!(tier.to_string(), label);
+        }
+    }
+
+    #[test]
+    fn serialize() {
+        let cases = [
+            (SubscriptionTier::Pro, r#"{"tier":"pro","label":"Pro"}"#),
+            (SubscriptionTier::Max { multiplier: Some(20) }, r#"{"tier":"max","multiplier":20,"label":"Max 20x"}"#),
+            (
+                unknown(Some("platinum"), None),
+                r#"{"tier":"unknown","subscription_type":"platinum","rate_limit_tier":null,"label":"platinum"}"#,
+            ),
+        ];
+        for (tier, json) in cases {
+            assert_eq!(serde_json::to_string(&tier).unwrap(), json);
+        }
+    }
+}
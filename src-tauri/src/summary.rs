@@ -0,0 +1,184 @@
+// Plain-text descriptions of usage, shared by the tray menu, tooltip and clipboard.
+
+use crate::{AccountInfo, UsageLimit, UsageResponse};
+use chrono::{DateTime, Local, Utc};
+
+pub fn format_local_time(time: DateTime<Utc>) -> String {
+    let local = time.with_timezone(&Local);
+    if local.date_naive() == Local::now().date_naive() {
+        local.format("%H:%M").to_string()
+    } else {
+        local.format("%a %H:%M").to_string()
+    }
+}
+
+pub fn format_reset(resets_at: &str) -> Option<String> {
+    DateTime::parse_from_rfc3339(resets_at)
+        .ok()
+        .map(|time| format_local_time(time.with_timezone(&Utc)))
+}
+
+// "42%", or "--" when the API left it out, as in the tray title
+fn percent(utilization: Option<f64>) -> String {
+    match utilization {
+        Some(utilization) => format!("{}%", utilization as i32),
+        None => "--".to_string(),
+    }
+}
+
+fn limit_line(label: &str, limit: &UsageLimit) -> String {
+    let percent = percent(limit.utilization);
+    match limit.resets_at.as_deref().and_then(format_reset) {
+        Some(reset) => format!("{}: {} (resets {})", label, percent, reset),
+        None => format!("{}: {}", label, percent),
+    }
+}
+
+// One line per bucket present in the response
+pub fn bucket_lines(usage: &UsageResponse) -> Vec<String> {
+    let limits = [
+        ("5-hour", &usage.five_hour),
+        ("7-day", &usage.seven_day),
+        ("7-day Sonnet", &usage.seven_day_sonnet),
+        ("7-day Opus", &usage.seven_day_opus),
+    ];
+
+    let mut lines: Vec<String> = limits
+        .iter()
+        .filter_map(|(label, limit)| limit.as_ref().map(|l| limit_line(label, l)))
+        .collect();
+
+    if let Some(ref extra) = usage.extra_usage {
+        if extra.is_enabled.unwrap_or(false) {
+            let percent = percent(extra.utilization);
+            match (extra.used_credits, extra.monthly_limit) {
+                (Some(used), Some(limit)) => {
+                    lines.push(format!("Extra usage: {} ({} / {} credits)", percent, used, limit))
+                }
+                _ => lines.push(format!("Extra usage: {}", percent)),
+            }
+        }
+    }
+    lines
+}
+
+pub fn account_line(account: &AccountInfo) -> Option<String> {
+    match (&account.email, &account.subscription) {
+        (Some(email), Some(subscription)) => Some(format!("{} ({})", email, subscription)),
+        (Some(email), None) => Some(email.clone()),
+        (None, Some(subscription)) => Some(subscription.to_string()),
+        (None, None) => None,
+    }
+}
+
+pub fn usage_text(
+    usage: &UsageResponse,
+    account: Option<&AccountInfo>,
+    last_updated: Option<DateTime<Utc>>,
+) -> String {
+    let mut lines = vec![match account.and_then(account_line) {
+        Some(account) => format!("Claude usage: {}", account),
+        None => "Claude usage".to_string(),
+    }];
+    lines.extend(bucket_lines(usage));
+    if let Some(updated) = last_updated {
+        lines.push(format!("Updated {}", format_local_time(updated)));
+    }
+    lines.join("\n")
+}
+
+pub fn tooltip_text(
+    usage: Option<&UsageResponse>,
+    account: Option<&AccountInfo>,
+    last_updated: Option<DateTime<Utc>>,
+    status: Option<&str>,
+    last_error: Option<&str>,
+    logged_out: bool,
+) -> String {
+    let mut lines = vec!["Claude Usage Monitor".to_string()];
+    if let Some(account) = account.and_then(account_line) {
+        lines.push(account);
+    }
+    match usage {
+        Some(usage) => lines.extend(bucket_lines(usage)),
+        None if logged_out => lines.push(crate::credentials::NOT_LOGGED_IN.to_string()),
+        None => lines.push("No usage data yet".to_string()),
+    }
+    if let Some(updated) = last_updated {
+        lines.push(format!("Updated {}", format_local_time(updated)));
+    }
+    if let Some(status) = status {
+        lines.push(status.to_string());
+    }
+    if let Some(error) = last_error {
+        lines.push(format!("Error: {}", error));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subscription::SubscriptionTier;
+    use crate::ExtraUsage;
+
+    fn limit(utilization: Option<f64>) -> Option<UsageLimit> {
+        Some(UsageLimit { utilization, resets_at: None })
+    }
+
+    #[test]
+    fn lists_present_buckets() {
+        let usage = UsageResponse {
+            five_hour: limit(Some(42.7)),
+            seven_day: limit(None),
+            seven_day_opus: limit(Some(0.0)),
+            extra_usage: Some(ExtraUsage {
+                is_enabled: Some(true),
+                monthly_limit: Some(5000),
+                used_credits: Some(1200),
+                utilization: Some(24.0),
+            }),
+            ..UsageResponse::default()
+        };
+        assert_eq!(
+            bucket_lines(&usage),
+            ["5-hour: 42%", "7-day: --", "7-day Opus: 0%", "Extra usage: 24% (1200 / 5000 credits)"]
+        );
+
+        let disabled = ExtraUsage { is_enabled: Some(false), ..ExtraUsage::default() };
+        let usage = UsageResponse { extra_usage: Some(disabled), ..UsageResponse::default() };
+        assert!(bucket_lines(&usage).is_empty());
+        let unknown = ExtraUsage { is_enabled: Some(true), ..ExtraUsage::default() };
+        let usage = UsageResponse { extra_usage: Some(unknown), ..UsageResponse::default() };
+        assert_eq!(bucket_lines(&usage), ["Extra usage: --"]);
+    }
+
+    #[test]
+    fn describes_the_account() {
+        let account = AccountInfo {
+            email: Some("me@example.com".to_string()),
+            subscription: Some(SubscriptionTier::Max { multiplier: Some(5) }),
+            ..AccountInfo::default()
+        };
+        assert_eq!(account_line(&account).as_deref(), Some("me@example.com (Max 5x)"));
+        let no_tier = AccountInfo { subscription: None, ..account.clone() };
+        assert_eq!(account_line(&no_tier).as_deref(), Some("me@example.com"));
+        let no_email = AccountInfo { email: None, ..account };
+        assert_eq!(account_line(&no_email).as_deref(), Some("Max 5x"));
+        assert_eq!(account_line(&AccountInfo::default()), None);
+    }
+
+    #[test]
+    fn tooltip_and_clipboard_text() {
+        let usage = UsageResponse { five_hour: limit(Some(10.0)), ..UsageResponse::default() };
+        assert_eq!(usage_text(&usage, None, None), "Claude usage\n5-hour: 10%");
+
+        let tooltip = tooltip_text(Some(&usage), None, None, Some("Offline, data is 14 min old"), Some("timed out"), false);
+        assert_eq!(tooltip, "Claude Usage Monitor\n5-hour: 10%\nOffline, data is 14 min old\nError: timed out");
+        assert_eq!(tooltip_text(None, None, None, None, None, false), "Claude Usage Monitor\nNo usage data yet");
+        assert_eq!(
+            tooltip_text(None, None, None, None, None, true),
+            format!("Claude Usage Monitor\n{}", crate::credentials::NOT_LOGGED_IN)
+        );
+    }
+}
@@ -0,0 +1,430 @@
+// Tray title templates.
+//
+// A template is plain text with placeholders and conditional segments:
+//
+//   {five_hour}%                       utilization of a bucket
+//   {five_hour.reset}                  countdown until the bucket resets
+//   {five_hour.projected}              utilization projected at reset time
+//   {five_hour.pace}                   pacing arrow (↑ ahead, → on track, ↓ behind)
+//   {?five_hour >= 90} ...{/}          segment shown only when the condition holds
+//   {?seven_day_opus} ...{/}           segment shown only when the value exists
+//   {{ and }}                          literal braces
+//
+// Buckets: five_hour, seven_day, seven_day_sonnet, seven_day_opus, extra_usage
+// (extra_usage only has a utilization). Missing values render as "--".
+
+use crate::{UsageLimit, UsageResponse};
+use chrono::{DateTime, Utc};
+
+pub const DEFAULT_TRAY_FORMAT: &str = "{five_hour}%";
+
+// Pacing within this many percentage points of the elapsed window is "on track"
+const PACE_TOLERANCE: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bucket {
+    FiveHour,
+    SevenDay,
+    SevenDaySonnet,
+    SevenDayOpus,
+    ExtraUsage,
+}
+
+impl Bucket {
+    fn parse(name: &str) -> Option<Bucket> {
+        match name {
+            "five_hour" => Some(Bucket::FiveHour),
+            "seven_day" => Some(Bucket::SevenDay),
+            "seven_day_sonnet" => Some(Bucket::SevenDaySonnet),
+            "seven_day_opus" => Some(Bucket::SevenDayOpus),
+            "extra_usage" => Some(Bucket::ExtraUsage),
+            _ => None,
+        }
+    }
+
+    fn window_hours(self) -> i64 {
+        match self {
+            Bucket::FiveHour => 5,
+            _ => 168,
+        }
+    }
+
+    fn limit(self, usage: &UsageResponse) -> Option<&UsageLimit> {
+        match self {
+            Bucket::FiveHour => usage.five_hour.as_ref(),
+            Bucket::SevenDay => usage.seven_day.as_ref(),
+            Bucket::SevenDaySonnet => usage.seven_day_sonnet.as_ref(),
+            Bucket::SevenDayOpus => usage.seven_day_opus.as_ref(),
+            Bucket::ExtraUsage => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Attr {
+    Utilization,
+    Reset,
+    Projected,
+    Pace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Field {
+    bucket: Bucket,
+    attr: Attr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+}
+
+impl Op {
+    fn apply(self, lhs: f64, rhs: f64) -> bool {
+        match self {
+            Op::Gt => lhs > rhs,
+            Op::Ge => lhs >= rhs,
+            Op::Lt => lhs < rhs,
+            Op::Le => lhs <= rhs,
+            Op::Eq => lhs == rhs,
+            Op::Ne => lhs != rhs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    field: Field,
+    comparison: Option<(Op, f64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Field(Field),
+    Conditional(Condition, Vec<Node>),
+}
+
+// A rendered placeholder: the numeric value used by conditions and its display text
+struct Value {
+    number: f64,
+    text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Self {
+            nodes: vec![
+                Node::Field(Field { bucket: Bucket::FiveHour, attr: Attr::Utilization }),
+                Node::Text("%".to_string()),
+            ],
+        }
+    }
+}
+
+fn parse_field(spec: &str, position: usize) -> Result<Field, String> {
+    let (name, attr) = match spec.split_once('.') {
+        Some((name, attr)) => (name, Some(attr)),
+        None => (spec, None),
+    };
+
+    let bucket = Bucket::parse(name)
+        .ok_or_else(|| format!("Unknown placeholder '{}' at position {}", name, position))?;
+
+    let attr = match attr {
+        None => Attr::Utilization,
+        Some("reset") => Attr::Reset,
+        Some("projected") => Attr::Projected,
+        Some("pace") => Attr::Pace,
+        Some(other) => {
+            return Err(format!("Unknown attribute '{}' for '{}' at position {}", other, name, position))
+        }
+    };
+
+    if bucket == Bucket::ExtraUsage && attr != Attr::Utilization {
+        return Err(format!("'extra_usage' only supports utilization (position {})", position));
+    }
+
+    Ok(Field { bucket, attr })
+}
+
+fn parse_condition(spec: &str, position: usize) -> Result<Condition, String> {
+    // Two-character operators first so ">=" is not read as ">"
+    const OPERATORS: [(&str, Op); 6] = [
+        (">=", Op::Ge),
+        ("<=", Op::Le),
+        ("==", Op::Eq),
+        ("!=", Op::Ne),
+        (">", Op::Gt),
+        ("<", Op::Lt),
+    ];
+
+    for (symbol, op) in OPERATORS {
+        if let Some((lhs, rhs)) = spec.split_once(symbol) {
+            let field = parse_field(lhs.trim(), position)?;
+            let threshold = rhs.trim().parse::<f64>().map_err(|_| {
+                format!("Expected a number after '{}' at position {}, got '{}'", symbol, position, rhs.trim())
+            })?;
+            return Ok(Condition { field, comparison: Some((op, threshold)) });
+        }
+    }
+
+    Ok(Condition { field: parse_field(spec.trim(), position)?, comparison: None })
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template, String> {
+        // Stack of open conditional segments; the bottom entry is the template root
+        let mut stack: Vec<(Option<Condition>, Vec<Node>)> = vec![(None, Vec::new())];
+        let mut text = String::new();
+        // Positions in errors count characters, as an editor would
+        let mut chars = source.chars().enumerate().peekable();
+
+        while let Some((position, c)) = chars.next() {
+            match c {
+                '{' if matches!(chars.peek(), Some((_, '{'))) => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if matches!(chars.peek(), Some((_, '}'))) => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => return Err(format!("Unmatched '}}' at position {}", position)),
+                '{' => {
+                    let mut tag = String::new();
+                    let mut closed = false;
+                    for (_, c) in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        tag.push(c);
+                    }
+                    if !closed {
+                        return Err(format!("Unclosed '{{' at position {}", position));
+                    }
+
+                    let nodes = &mut stack.last_mut().expect("template root is never popped").1;
+                    if !text.is_empty() {
+                        nodes.push(Node::Text(std::mem::take(&mut text)));
+                    }
+
+                    let tag = tag.trim();
+                    if let Some(condition) = tag.strip_prefix('?') {
+                        stack.push((Some(parse_condition(condition, position)?), Vec::new()));
+                    } else if tag == "/" {
+                        if stack.len() == 1 {
+                            return Err(format!("'{{/}}' without an open condition at position {}", position));
+                        }
+                        let (condition, children) = stack.pop().expect("checked above");
+                        let condition = condition.expect("only the root has no condition");
+                        stack
+                            .last_mut()
+                            .expect("template root is never popped")
+                            .1
+                            .push(Node::Conditional(condition, children));
+                    } else {
+                        nodes.push(Node::Field(parse_field(tag, position)?));
+                    }
+                }
+                _ => text.push(c),
+            }
+        }
+
+        if stack.len() > 1 {
+            return Err("Unclosed condition: every '{?...}' needs a matching '{/}'".to_string());
+        }
+
+        let mut nodes = stack.pop().expect("template root is never popped").1;
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+        Ok(Template { nodes })
+    }
+
+    pub fn render(&self, usage: &UsageResponse, now: DateTime<Utc>) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, usage, now, &mut out);
+        out
+    }
+}
+
+fn render_nodes(nodes: &[Node], usage: &UsageResponse, now: DateTime<Utc>, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Field(field) => match field_value(*field, usage, now) {
+                Some(value) => out.push_str(&value.text),
+                None => out.push_str("--"),
+            },
+            Node::Conditional(condition, children) => {
+                let holds = match (field_value(condition.field, usage, now), condition.comparison) {
+                    (Some(value), Some((op, threshold))) => op.apply(value.number, threshold),
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                };
+                if holds {
+                    render_nodes(children, usage, now, out);
+                }
+            }
+        }
+    }
+}
+
+// Same countdown format as the popup: "1d4h", "2h13m", "42m"
+pub fn format_countdown(remaining: chrono::Duration) -> String {
+    let secs = remaining.num_seconds();
+    if secs <= 0 {
+        return "0m".to_string();
+    }
+    let days = secs / 86400;
+    let hours = (secs % 86400) / 3600;
+    let minutes = (secs % 3600) / 60;
+
+    if days > 0 {
+        format!("{}d{}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h{}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+fn remaining(limit: &UsageLimit, now: DateTime<Utc>) -> Option<chrono::Duration> {
+    let resets_at = DateTime::parse_from_rfc3339(limit.resets_at.as_deref()?).ok()?;
+    Some(resets_at.with_timezone(&Utc) - now)
+}
+
+// Fraction of the bucket's window that has already elapsed, in 0.0..=1.0
+fn elapsed_fraction(bucket: Bucket, limit: &UsageLimit, now: DateTime<Utc>) -> Option<f64> {
+    let window = chrono::Duration::hours(bucket.window_hours());
+    let remaining = remaining(limit, now)?;
+    let elapsed = (window - remaining).num_seconds() as f64 / window.num_seconds() as f64;
+    Some(elapsed.clamp(0.0, 1.0))
+}
+
+fn field_value(field: Field, usage: &UsageResponse, now: DateTime<Utc>) -> Option<Value> {
+    if field.bucket == Bucket::ExtraUsage {
+        let utilization = usage.extra_usage.as_ref()?.utilization?;
+        return Some(Value { number: utilization, text: format!("{}", utilization as i32) });
+    }
+
+    let limit = field.bucket.limit(usage)?;
+    match field.attr {
+        Attr::Utilization => {
+            let utilization = limit.utilization?;
+            Some(Value { number: utilization, text: format!("{}", utilization as i32) })
+        }
+        Attr::Reset => {
+            let remaining = remaining(limit, now)?;
+            Some(Value {
+                number: remaining.num_minutes().max(0) as f64,
+                text: format_countdown(remaining),
+            })
+        }
+        Attr::Projected => {
+            let elapsed = elapsed_fraction(field.bucket, limit, now)?;
+            if elapsed <= 0.0 {
+                return None;
+            }
+            let projected = limit.utilization? / elapsed;
+            Some(Value { number: projected, text: format!("{}", projected as i32) })
+        }
+        Attr::Pace => {
+            let elapsed = elapsed_fraction(field.bucket, limit, now)?;
+            let delta = limit.utilization? - elapsed * 100.0;
+            let arrow = if delta > PACE_TOLERANCE {
+                "↑"
+            } else if delta < -PACE_TOLERANCE {
+                "↓"
+            } else {
+                "→"
+            };
+            Some(Value { number: delta, text: arrow.to_string() })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(utilization: Option<f64>, resets_in: chrono::Duration, now: DateTime<Utc>) -> Option<UsageLimit> {
+        Some(UsageLimit { utilization, resets_at: Some((now + resets_in).to_rfc3339()) })
+    }
+
+    #[test]
+    fn parse() {
+        let cases: [(&str, Result<(), &str>); 14] = [
+            ("{five_hour}%", Ok(())),
+            ("5h {five_hour}% · 7d {seven_day}% {seven_day.reset}", Ok(())),
+            ("{?five_hour >= 90}!{/}{five_hour.pace}", Ok(())),
+            ("{?seven_day_opus}{seven_day_opus}%{/}", Ok(())),
+            ("{{literal}}", Ok(())),
+            ("{extra_usage}", Ok(())),
+            ("{six_hour}", Err("Unknown placeholder 'six_hour' at position 0")),
+            ("{five_hour.eta}", Err("Unknown attribute 'eta' for 'five_hour' at position 0")),
+            ("{extra_usage.reset}", Err("'extra_usage' only supports utilization (position 0)")),
+            ("{?five_hour > lots}{/}", Err("Expected a number after '>' at position 0, got 'lots'")),
+            ("ab {five_hour", Err("Unclosed '{' at position 3")),
+            ("ab }", Err("Unmatched '}' at position 3")),
+            ("{/}", Err("'{/}' without an open condition at position 0")),
+            ("{?five_hour}", Err("Unclosed condition: every '{?...}' needs a matching '{/}'")),
+        ];
+        for (source, expected) in cases {
+            let result = Template::parse(source).map(|_| ());
+            assert_eq!(result, expected.map_err(str::to_string), "{}", source);
+        }
+    }
+
+    #[test]
+    fn positions_count_characters() {
+        assert_eq!(Template::parse("5h·7d {nope}"), Err("Unknown placeholder 'nope' at position 6".to_string()));
+        assert_eq!(Template::parse("⚡ }"), Err("Unmatched '}' at position 2".to_string()));
+    }
+
+    #[test]
+    fn render() {
+        let now = Utc::now();
+        let usage = UsageResponse {
+            // Halfway through the window
+            five_hour: limit(Some(42.7), chrono::Duration::minutes(150), now),
+            seven_day: limit(Some(91.0), chrono::Duration::hours(30), now),
+            seven_day_sonnet: limit(None, chrono::Duration::hours(30), now),
+            seven_day_opus: None,
+            extra_usage: None,
+        };
+
+        let cases = [
+            ("{five_hour}%", "42%"),
+            ("{five_hour.reset}", "2h30m"),
+            ("{five_hour.projected}%", "85%"),
+            ("{five_hour.pace}", "↓"),
+            ("{seven_day.pace}", "↑"),
+            ("{?seven_day >= 90}!{/}{seven_day}%", "!91%"),
+            ("{?five_hour >= 90}!{/}{five_hour}%", "42%"),
+            ("{seven_day_sonnet}%", "--%"),
+            ("{seven_day_sonnet.projected}", "--"),
+            ("{?seven_day_sonnet}s{/}", ""),
+            ("{seven_day_opus}% {seven_day_opus.reset}", "--% --"),
+            ("{?seven_day_opus}{seven_day_opus}%{/}", ""),
+            ("{extra_usage}", "--"),
+            ("{{{five_hour}}}", "{42}"),
+        ];
+        for (source, expected) in cases {
+            let template = Template::parse(source).unwrap();
+            assert_eq!(template.render(&usage, now), expected, "{}", source);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+// Runtime-rendered tray icon gauges.
+//
+// The ring style draws five_hour as the outer arc and seven_day as an inner
+// arc; the bar style draws them as two stacked horizontal bars. Icons are
+// rendered at the pixel density of the display the tray is on and can be
+// drawn in black-only "template" form so macOS tints them to match the menu
+// bar. While fetches fail the filled parts turn grey, so old numbers don't
+// pass for current ones.
+
+use crate::settings::ThresholdSettings;
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use tauri::image::Image;
+
+// Logical size of a menu bar icon; multiplied by the display scale factor
+pub const ICON_POINTS: f64 = 18.0;
+
+const GREEN: [u8; 4] = [0x34, 0xc7, 0x59, 0xff];
+const YELLOW: [u8; 4] = [0xff, 0x9f, 0x0a, 0xff];
+const RED: [u8; 4] = [0xff, 0x3b, 0x30, 0xff];
+const TRACK: [u8; 4] = [0x80, 0x80, 0x80, 0x66];
+const TEMPLATE_FILL: [u8; 4] = [0x00, 0x00, 0x00, 0xff];
+const TEMPLATE_TRACK: [u8; 4] = [0x00, 0x00, 0x00, 0x4d];
+const STALE_FILL: [u8; 4] = [0x8e, 0x8e, 0x93, 0xff];
+const TEMPLATE_STALE_FILL: [u8; 4] = [0x00, 0x00, 0x00, 0x99];
+
+// Subsamples per pixel axis, for anti-aliased edges
+const SUPERSAMPLE: u32 = 4;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IconStyle {
+    Static,
+    #[default]
+    Ring,
+    Bar,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct TrayIconSettings {
+    pub style: IconStyle,
+    pub show_seven_day: bool,
+    pub monochrome: bool,
+}
+
+impl Default for TrayIconSettings {
+    fn default() -> Self {
+        Self {
+            style: IconStyle::default(),
+            show_seven_day: true,
+            monochrome: false,
+        }
+    }
+}
+
+pub fn static_icon() -> Image<'static> {
+    let icon_bytes = include_bytes!("../icons/32x32.png");
+    let icon_image = image::load_from_memory(icon_bytes)
+        .expect("Failed to load icon")
+        .to_rgba8();
+    let (width, height) = icon_image.dimensions();
+    Image::new_owned(icon_image.into_raw(), width, height)
+}
+
+pub fn to_tauri_image(image: RgbaImage) -> Image<'static> {
+    let (width, height) = image.dimensions();
+    Image::new_owned(image.into_raw(), width, height)
+}
+
+fn fill_color(utilization: f64, thresholds: &ThresholdSettings, monochrome: bool) -> [u8; 4] {
+    if monochrome {
+        TEMPLATE_FILL
+    } else if utilization >= thresholds.critical {
+        RED
+    } else if utilization >= thresholds.warning {
+        YELLOW
+    } else {
+        GREEN
+    }
+}
+
+// One gauge track: which utilization it shows and the color of its filled part
+struct Gauge {
+    fraction: f64,
+    fill: [u8; 4],
+}
+
+impl Gauge {
+    fn new(utilization: Option<f64>, thresholds: &ThresholdSettings, monochrome: bool) -> Self {
+        let utilization = utilization.unwrap_or(0.0).clamp(0.0, 100.0);
+        Self {
+            fraction: utilization / 100.0,
+            fill: fill_color(utilization, thresholds, monochrome),
+        }
+    }
+
+    fn color_at(&self, position: f64, track: [u8; 4]) -> [u8; 4] {
+        if position < self.fraction {
+            self.fill
+        } else {
+            track
+        }
+    }
+}
+
+// Renders a square gauge icon `size` pixels wide, colored by the same
+// thresholds as the popup bars, or grey when `stale`. `seven_day` is ignored
+// unless `settings.show_seven_day` is set.
+pub fn render_gauge(
+    settings: &TrayIconSettings,
+    thresholds: &ThresholdSettings,
+    five_hour: Option<f64>,
+    seven_day: Option<f64>,
+    stale: bool,
+    size: u32,
+) -> RgbaImage {
+    let track = if settings.monochrome { TEMPLATE_TRACK } else { TRACK };
+    let mut gauges = vec![Gauge::new(five_hour, thresholds, settings.monochrome)];
+    if settings.show_seven_day {
+        gauges.push(Gauge::new(seven_day, thresholds, settings.monochrome));
+    }
+    if stale {
+        for gauge in &mut gauges {
+            gauge.fill = if settings.monochrome { TEMPLATE_STALE_FILL } else { STALE_FILL };
+        }
+    }
+
+    let size_f = size as f64;
+    let sample = |x: f64, y: f64| -> Option<[u8; 4]> {
+        match settings.style {
+            IconStyle::Bar => bar_sample(&gauges, track, x, y, size_f),
+            _ => ring_sample(&gauges, track, x, y, size_f),
+        }
+    };
+
+    let mut image = RgbaImage::new(size, size);
+    let samples = (SUPERSAMPLE * SUPERSAMPLE) as f64;
+    for (px, py, pixel) in image.enumerate_pixels_mut() {
+        // Accumulate premultiplied color so transparent samples don't darken edges
+        let mut acc = [0.0f64; 4];
+        for sy in 0..SUPERSAMPLE {
+            for sx in 0..SUPERSAMPLE {
+                let x = px as f64 + (sx as f64 + 0.5) / SUPERSAMPLE as f64;
+                let y = py as f64 + (sy as f64 + 0.5) / SUPERSAMPLE as f64;
+                if let Some(color) = sample(x, y) {
+                    let alpha = color[3] as f64 / 255.0;
+                    acc[0] += color[0] as f64 * alpha;
+                    acc[1] += color[1] as f64 * alpha;
+                    acc[2] += color[2] as f64 * alpha;
+                    acc[3] += alpha;
+                }
+            }
+        }
+        if acc[3] > 0.0 {
+            *pixel = Rgba([
+                (acc[0] / acc[3]).round() as u8,
+                (acc[1] / acc[3]).round() as u8,
+                (acc[2] / acc[3]).round() as u8,
+                (acc[3] / samples * 255.0).round() as u8,
+            ]);
+        }
+    }
+    image
+}
+
+fn ring_sample(gauges: &[Gauge], track: [u8; 4], x: f64, y: f64, size: f64) -> Option<[u8; 4]> {
+    let center = size / 2.0;
+    let (dx, dy) = (x - center, y - center);
+    let radius = (dx * dx + dy * dy).sqrt();
+
+    // Thinner rings when two have to fit
+    let thickness = size * if gauges.len() > 1 { 0.16 } else { 0.22 };
+    let gap = size * 0.06;
+
+    // Clockwise from 12 o'clock, in 0.0..1.0
+    let angle = dx.atan2(-dy);
+    let position = if angle < 0.0 { angle + std::f64::consts::TAU } else { angle } / std::f64::consts::TAU;
+
+    let mut outer = center - size * 0.03;
+    for gauge in gauges {
+        let inner = outer - thickness;
+        if radius <= outer && radius >= inner {
+            return Some(gauge.color_at(position, track));
+        }
+        outer = inner - gap;
+    }
+    None
+}
+
+fn bar_sample(gauges: &[Gauge], track: [u8; 4], x: f64, y: f64, size: f64) -> Option<[u8; 4]> {
+    let margin = size * 0.06;
+    let gap = size * 0.12;
+    let count = gauges.len() as f64;
+    // Bars are vertically centered; a single bar is as tall as the pair would be
+    let height = if gauges.len() > 1 { size * 0.3 } else { size * 0.45 };
+    let top = (size - (height * count + gap * (count - 1.0))) / 2.0;
+
+    if x < margin || x > size - margin {
+        return None;
+    }
+    let position = (x - margin) / (size - 2.0 * margin);
+
+    for (i, gauge) in gauges.iter().enumerate() {
+        let bar_top = top + i as f64 * (height + gap);
+        if y >= bar_top && y <= bar_top + height {
+            return Some(gauge.color_at(position, track));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u32 = 36;
+    const TRACK_PIXEL: [u8; 4] = [0x80, 0x80, 0x80, 0x66];
+
+    fn render(style: IconStyle, five_hour: Option<f64>, stale: bool) -> RgbaImage {
+        let settings = TrayIconSettings { style, show_seven_day: true, monochrome: false };
+        render_gauge(&settings, &ThresholdSettings::default(), five_hour, Some(10.0), stale, SIZE)
+    }
+
+    #[test]
+    fn ring() {
+        let icon = render(IconStyle::Ring, Some(30.0), false);
+        assert_eq!(icon.dimensions(), (SIZE, SIZE));
+        // Outer ring: filled clockwise from 12 o'clock, so at 3 o'clock but not at 9
+        assert_eq!(icon.get_pixel(32, 18).0, GREEN);
+        assert_eq!(icon.get_pixel(3, 18).0, TRACK_PIXEL);
+        // Nothing in the middle
+        assert_eq!(icon.get_pixel(18, 18).0[3], 0);
+
+        let icon = render(IconStyle::Ring, Some(95.0), false);
+        assert_eq!(icon.get_pixel(3, 18).0, RED);
+        let icon = render(IconStyle::Ring, None, false);
+        assert_eq!(icon.get_pixel(32, 18).0, TRACK_PIXEL);
+    }
+
+    #[test]
+    fn bar() {
+        let icon = render(IconStyle::Bar, Some(30.0), false);
+        assert_eq!(icon.dimensions(), (SIZE, SIZE));
+        // Top bar is five_hour, bottom bar seven_day, both filled from the left
+        assert_eq!(icon.get_pixel(5, 10).0, GREEN);
+        assert_eq!(icon.get_pixel(30, 10).0, TRACK_PIXEL);
+        assert_eq!(icon.get_pixel(3, 25).0, GREEN);
+        assert_eq!(icon.get_pixel(10, 25).0, TRACK_PIXEL);
+        // Gap between the bars
+        assert_eq!(icon.get_pixel(18, 18).0[3], 0);
+    }
+
+    #[test]
+    fn stale_gauges_are_grey() {
+        assert_eq!(render(IconStyle::Ring, Some(95.0), true).get_pixel(32, 18).0, STALE_FILL);
+        assert_eq!(render(IconStyle::Bar, Some(95.0), true).get_pixel(5, 10).0, STALE_FILL);
+    }
+
+    #[test]
+    fn scales_with_the_display() {
+        let settings = TrayIconSettings::default();
+        for scale in [1.0, 2.0, 1.5] {
+            let size = (ICON_POINTS * scale).round() as u32;
+            let icon = render_gauge(&settings, &ThresholdSettings::default(), Some(50.0), None, false, size);
+            assert_eq!(icon.dimensions(), (size, size));
+        }
+    }
+}
@@ -0,0 +1,235 @@
+// The tray's right-click menu, rebuilt from AppState on every update.
+
+use crate::credentials::CredentialSource;
+use crate::status::MonitorStatus;
+use crate::{accounts, summary, AccountInfo, AppState, UsageResponse};
+use chrono::{DateTime, Utc};
+use tauri::{
+    menu::{Menu, MenuBuilder, MenuItemBuilder, SubmenuBuilder},
+    tray::TrayIconId,
+    AppHandle, Emitter, Manager, Wry,
+};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_opener::OpenerExt;
+
+const PAUSE_OPTIONS_MINUTES: [i64; 3] = [15, 30, 60];
+const PAUSE_ID_PREFIX: &str = "pause_";
+const ACCOUNT_ID_PREFIX: &str = "account_";
+
+#[derive(Default)]
+pub struct TrayMenuData {
+    // (id, name) of every account, and the one commands act on by default
+    pub accounts: Vec<(String, String)>,
+    pub active_account: String,
+    // The rest is about the account the tray shows
+    pub usage: Option<UsageResponse>,
+    pub account: Option<AccountInfo>,
+    pub last_error: Option<String>,
+    pub last_updated: Option<DateTime<Utc>>,
+    // e.g. "Offline, data is 14 min old"; None while usage is current
+    pub status: Option<String>,
+    pub logged_out: bool,
+    // Signed in from the app itself, which can be signed out of here
+    pub app_login: bool,
+    pub paused_until: Option<DateTime<Utc>>,
+}
+
+impl TrayMenuData {
+    pub async fn from_state(state: &AppState) -> Self {
+        let settings = state.settings.lock().await.clone();
+        let slots = state.accounts.lock().await;
+        let displayed = accounts::displayed(&slots, &settings).cloned();
+        let interval = std::time::Duration::from_secs(settings.polling.interval_secs);
+        let now = Utc::now();
+        Self {
+            accounts: slots.iter().map(|slot| (slot.id.clone(), slot.name.clone())).collect(),
+            active_account: accounts::active_id(&settings),
+            usage: displayed.as_ref().and_then(|slot| slot.usage.clone()),
+            account: displayed.as_ref().and_then(|slot| slot.account.clone()),
+            last_error: displayed.as_ref().and_then(|slot| slot.last_error.clone()),
+            last_updated: displayed.as_ref().and_then(|slot| slot.last_updated),
+            status: displayed
+                .as_ref()
+                .filter(|slot| slot.logged_in)
+                .and_then(|slot| MonitorStatus::new(slot, interval, now).message),
+            logged_out: displayed.as_ref().is_some_and(|slot| !slot.logged_in),
+            app_login: accounts::configured(&settings).iter().any(|account| {
+                account.credentials == CredentialSource::App
+                    && slots.iter().any(|slot| slot.id == account.id && slot.logged_in)
+            }),
+            paused_until: *state.paused_until.lock().await,
+        }
+    }
+}
+
+fn info_item(app: &AppHandle, id: usize, text: &str) -> tauri::Result<tauri::menu::MenuItem<Wry>> {
+    MenuItemBuilder::with_id(format!("info_{}", id), text)
+        .enabled(false)
+        .build(app)
+}
+
+pub fn build_menu(app: &AppHandle, data: &TrayMenuData) -> tauri::Result<Menu<Wry>> {
+    let mut info_lines = match data.usage {
+        Some(ref usage) => summary::bucket_lines(usage),
+        None if data.logged_out => vec![crate::credentials::NOT_LOGGED_IN.to_string()],
+        None => vec!["No usage data yet".to_string()],
+    };
+    if let Some(line) = data.account.as_ref().and_then(summary::account_line) {
+        info_lines.push(line);
+    }
+    if let Some(updated) = data.last_updated {
+        info_lines.push(format!("Updated {}", summary::format_local_time(updated)));
+    }
+    if let Some(ref status) = data.status {
+        info_lines.push(status.clone());
+    }
+    if let Some(ref error) = data.last_error {
+        info_lines.push(format!("Error: {}", error));
+    }
+
+    let mut menu = MenuBuilder::new(app);
+    for (i, line) in info_lines.iter().enumerate() {
+        menu = menu.item(&info_item(app, i, line)?);
+    }
+
+    if data.accounts.len() > 1 {
+        let accounts_menu = data
+            .accounts
+            .iter()
+            .fold(SubmenuBuilder::new(app, "Accounts"), |submenu, (id, name)| {
+                let label = if *id == data.active_account { format!("✓ {}", name) } else { name.clone() };
+                submenu.text(format!("{}{}", ACCOUNT_ID_PREFIX, id), label)
+            })
+            .build()?;
+        menu = menu.separator().item(&accounts_menu);
+    }
+
+    let pause_menu = match data.paused_until {
+        Some(until) if until > Utc::now() => SubmenuBuilder::new(app, "Polling paused")
+            .item(&info_item(app, info_lines.len(), &format!("Until {}", summary::format_local_time(until)))?)
+            .text("resume", "Resume polling"),
+        _ => PAUSE_OPTIONS_MINUTES.iter().fold(
+            SubmenuBuilder::new(app, "Pause polling"),
+            |submenu, minutes| submenu.text(format!("{}{}", PAUSE_ID_PREFIX, minutes), format!("{} minutes", minutes)),
+        ),
+    }
+    .build()?;
+
+    menu = menu
+        .separator()
+        .text("refresh", "Refresh now")
+        .text("open_claude", "Open Claude")
+        .text("open_usage_settings", "Open usage settings")
+        .text("copy_usage", "Copy usage as text")
+        .item(&pause_menu)
+        .separator()
+        .text("open_settings", "Open settings");
+    if data.app_login {
+        menu = menu.text("sign_out", "Sign out");
+    }
+    menu.text("quit", "Quit").build()
+}
+
+pub async fn update_tray_menu(app: &AppHandle) {
+    if let Some(tray) = app.tray_by_id(&TrayIconId::new("main")) {
+        let data = TrayMenuData::from_state(&app.state::<AppState>()).await;
+        if let Ok(menu) = build_menu(app, &data) {
+            let _ = tray.set_menu(Some(menu));
+        }
+    }
+}
+
+pub async fn copy_usage(app: &AppHandle) -> Result<(), String> {
+    let data = TrayMenuData::from_state(&app.state::<AppState>()).await;
+    let usage = data.usage.ok_or_else(|| "No usage data yet".to_string())?;
+    let text = summary::usage_text(&usage, data.account.as_ref(), data.last_updated);
+    app.clipboard().write_text(text).map_err(|e| e.to_string())
+}
+
+async fn open_settings_file(app: &AppHandle) -> Result<(), String> {
+    let path = crate::settings::settings_path();
+    // Create it first so there is something to open, with the settings in
+    // effect. Under the lock, so the settings watcher reloads exactly those
+    let state = app.state::<AppState>();
+    let user = state.user_settings.lock().await;
+    if !path.exists() {
+        crate::settings::save_settings(&user)?;
+    }
+    drop(user);
+    app.opener()
+        .open_path(path.to_string_lossy(), None::<&str>)
+        .map_err(|e| e.to_string())
+}
+
+async fn set_paused_until(app: &AppHandle, until: Option<DateTime<Utc>>) {
+    *app.state::<AppState>().paused_until.lock().await = until;
+    update_tray_menu(app).await;
+}
+
+pub fn handle_menu_event(app: &AppHandle, id: &str) {
+    match id {
+        "quit" => app.exit(0),
+        "open_claude" | "open_usage_settings" => {
+            let url = if id == "open_claude" {
+                "https://claude.ai/"
+            } else {
+                "https://claude.ai/settings/usage"
+            };
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let _ = crate::open_url(url.to_string(), app.state::<AppState>(), app.clone()).await;
+            });
+        }
+        "open_settings" => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let _ = open_settings_file(&app).await;
+            });
+        }
+        "refresh" => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let _ = crate::refresh_usage(None, app.state::<AppState>(), app.clone()).await;
+            });
+        }
+        "sign_out" => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(error) = crate::oauth_logout(app.clone()).await {
+                    let settings = app.state::<AppState>().settings.lock().await.clone();
+                    let configured = accounts::configured(&settings);
+                    if let Some(account) = configured.iter().find(|a| a.credentials == CredentialSource::App) {
+                        let _ = app.emit("usage-error", crate::UsageError { account_id: account.id.clone(), error });
+                    }
+                }
+            });
+        }
+        "copy_usage" => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let _ = copy_usage(&app).await;
+            });
+        }
+        "resume" => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                set_paused_until(&app, None).await;
+                let _ = crate::refresh_usage(None, app.state::<AppState>(), app.clone()).await;
+            });
+        }
+        _ => {
+            if let Some(account_id) = id.strip_prefix(ACCOUNT_ID_PREFIX) {
+                let app = app.clone();
+                let account_id = account_id.to_string();
+                tauri::async_runtime::spawn(async move {
+                    let _ = crate::set_active_account(account_id, app.state::<AppState>(), app.clone()).await;
+                });
+            } else if let Some(minutes) = id.strip_prefix(PAUSE_ID_PREFIX).and_then(|m| m.parse::<i64>().ok()) {
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    set_paused_until(&app, Some(Utc::now() + chrono::Duration::minutes(minutes))).await;
+                });
+            }
+        }
+    }
+}
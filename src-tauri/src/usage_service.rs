@@ -0,0 +1,325 @@
+// Fetching usage and publishing it, whatever asked for it: the per-account
+// polling tasks, refresh_usage and the tray's "Refresh now" all go through
+// UsageService::refresh, which updates the account's slot, the tray and the
+// frontend the same way every time. A refresh asked for
+// while the account is already being fetched waits for that fetch and gets
+// its result rather than starting another. Any completed fetch restarts the
+// account's polling interval, so a manual refresh isn't followed by a poll
+// straight away. It also keeps each account's MonitorStatus (see status.rs)
+// and sends `status-changed` when it changes.
+
+use crate::accounts::AccountSettings;
+use crate::credentials_watcher::CredentialWatcher;
+use crate::settings::Settings;
+use crate::status::{self, Failure, MonitorStatus};
+use crate::{accounts, credentials, AccountUpdate, AppState, UsageError, UsageResponse, UsageUpdate};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{broadcast, Notify};
+use tokio::time::{sleep, Duration, Instant};
+
+type FetchResult = Result<(), String>;
+
+#[derive(Default)]
+pub struct UsageService {
+    // One polling task per account
+    pollers: Mutex<Vec<JoinHandle<()>>>,
+    in_flight: InFlight,
+    // Woken by account id when a fetch completes, to reschedule its poller
+    fetched: Mutex<HashMap<String, Arc<Notify>>>,
+    // Last status sent per account, to only send changes
+    published: Mutex<HashMap<String, MonitorStatus>>,
+}
+
+// Fetches in flight by account id, each with whoever is waiting on it
+#[derive(Default)]
+struct InFlight(Arc<Mutex<HashMap<String, broadcast::Sender<FetchResult>>>>);
+
+impl InFlight {
+    // Runs `fetch` as its own task, or joins the one already running for the
+    // account, in which case `fetch` is dropped without being run
+    async fn join<F>(&self, account_id: &str, fetch: F) -> FetchResult
+    where
+        F: Future<Output = FetchResult> + Send + 'static,
+    {
+        let mut done = {
+            let mut in_flight = self.0.lock().unwrap_or_else(|e| e.into_inner());
+            match in_flight.get(account_id) {
+                Some(running) => running.subscribe(),
+                None => {
+                    let (tx, rx) = broadcast::channel(1);
+                    in_flight.insert(account_id.to_string(), tx);
+                    let (in_flight, account_id) = (self.0.clone(), account_id.to_string());
+                    tauri::async_runtime::spawn(async move {
+                        let result = fetch.await;
+                        let tx = in_flight.lock().unwrap_or_else(|e| e.into_inner()).remove(&account_id);
+                        if let Some(tx) = tx {
+                            let _ = tx.send(result);
+                        }
+                    });
+                    rx
+                }
+            }
+        };
+        done.recv().await.unwrap_or_else(|e| Err(format!("Usage fetch failed: {}", e)))
+    }
+}
+
+impl UsageService {
+    // (Re)starts one polling task per configured account
+    pub fn start(&self, app: &AppHandle, settings: &Settings) {
+        let mut pollers = self.pollers.lock().unwrap_or_else(|e| e.into_inner());
+        for poller in pollers.drain(..) {
+            poller.abort();
+        }
+        for account in accounts::configured(settings) {
+            pollers.push(tauri::async_runtime::spawn(poll(app.clone(), account)));
+        }
+    }
+
+    // Fetches and publishes the account's usage, or joins the fetch already
+    // in flight for it. The fetch runs as its own task, so it completes for
+    // everyone waiting even if the caller goes away, e.g. a restarted poller
+    pub async fn refresh(&self, app: &AppHandle, account: &AccountSettings) -> FetchResult {
+        let fetched = self.fetched(&account.id);
+        let (app, settings) = (app.clone(), account.clone());
+        self.in_flight
+            .join(&account.id, async move {
+                let result = fetch(&app, &settings).await;
+                // Only wakes the poller while it waits for its next poll
+                fetched.notify_waiters();
+                result
+            })
+            .await
+    }
+
+    fn fetched(&self, account_id: &str) -> Arc<Notify> {
+        let mut fetched = self.fetched.lock().unwrap_or_else(|e| e.into_inner());
+        fetched.entry(account_id.to_string()).or_default().clone()
+    }
+}
+
+async fn fetch(app: &AppHandle, account: &AccountSettings) -> FetchResult {
+    let state = app.state::<AppState>();
+    // Nothing to fetch until the credential watcher sees a login
+    let logged_in = state.accounts.lock().await.iter().any(|a| a.id == account.id && a.logged_in);
+    if !logged_in {
+        return Err(credentials::NOT_LOGGED_IN.to_string());
+    }
+    let api = state.settings.lock().await.api.clone();
+    match crate::fetch_usage(&api, &account.credentials).await {
+        Ok(usage) => {
+            record_usage(app, &account.id, usage).await;
+            Ok(())
+        }
+        Err((e, failure)) => {
+            record_error(app, &account.id, e.clone(), failure).await;
+            Err(e)
+        }
+    }
+}
+
+async fn record_usage(app: &AppHandle, account_id: &str, usage: UsageResponse) {
+    let state = app.state::<AppState>();
+    if let Some(account) = state.accounts.lock().await.iter_mut().find(|a| a.id == account_id) {
+        account.record_usage(usage.clone());
+    }
+    let update = UsageUpdate { account_id: account_id.to_string(), usage };
+    crate::update_tray(app).await;
+    let _ = app.emit("usage-updated", update);
+    publish_status(app, account_id).await;
+}
+
+async fn record_error(app: &AppHandle, account_id: &str, error: String, failure: Failure) {
+    let state = app.state::<AppState>();
+    if let Some(account) = state.accounts.lock().await.iter_mut().find(|a| a.id == account_id) {
+        account.record_failure(error.clone(), failure);
+    }
+    crate::update_tray(app).await;
+    let _ = app.emit("usage-error", UsageError { account_id: account_id.to_string(), error });
+    publish_status(app, account_id).await;
+}
+
+// Sends the account's status if it changed since it was last sent. The
+// state can also change just by time passing, e.g. to Stale while polling
+// is paused, so the tray is updated along with it
+async fn publish_status(app: &AppHandle, account_id: &str) {
+    let state = app.state::<AppState>();
+    let interval = Duration::from_secs(state.settings.lock().await.polling.interval_secs);
+    let Some(status) = state
+        .accounts
+        .lock()
+        .await
+        .iter()
+        .find(|a| a.id == account_id)
+        .map(|slot| MonitorStatus::new(slot, interval, Utc::now()))
+    else {
+        return;
+    };
+    let previous = state
+        .usage_service
+        .published
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(account_id.to_string(), status.clone());
+    if previous.as_ref() == Some(&status) {
+        return;
+    }
+    if previous.is_some_and(|previous| previous.state != status.state) {
+        crate::update_tray(app).await;
+    }
+    let _ = app.emit("status-changed", status);
+}
+
+// Works out when the account is next polled, backing off while fetches
+// fail, and records it for the status. Returns how long to wait
+async fn schedule(app: &AppHandle, account_id: &str) -> Duration {
+    let state = app.state::<AppState>();
+    let interval = Duration::from_secs(state.settings.lock().await.polling.interval_secs);
+    let paused_until = *state.paused_until.lock().await;
+    let delay = match state.accounts.lock().await.iter_mut().find(|a| a.id == account_id) {
+        Some(slot) => {
+            let delay = status::next_delay(slot, interval);
+            let next_poll = Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default();
+            slot.next_poll = Some(paused_until.map_or(next_poll, |until| until.max(next_poll)));
+            delay
+        }
+        None => interval,
+    };
+    publish_status(app, account_id).await;
+    delay
+}
+
+async fn sync_profile(app: &AppHandle, account: &AccountSettings) {
+    let state = app.state::<AppState>();
+    let api = state.settings.lock().await.api.clone();
+    let Ok(info) = crate::fetch_profile(&api, &account.credentials).await else {
+        return;
+    };
+
+    if let Some(email) = info.email.as_deref() {
+        crate::auto_select_browser_profile(app, email).await;
+    }
+
+    let subscription_changed = match state.accounts.lock().await.iter_mut().find(|a| a.id == account.id) {
+        Some(slot) => slot.record_account(info.clone()),
+        None => false,
+    };
+    crate::update_tray(app).await;
+    let _ = app.emit(
+        "account-updated",
+        AccountUpdate { account_id: account.id.clone(), logged_in: true, subscription_changed, account: Some(info) },
+    );
+}
+
+async fn log_out(app: &AppHandle, account_id: &str) {
+    let state = app.state::<AppState>();
+    if let Some(slot) = state.accounts.lock().await.iter_mut().find(|a| a.id == account_id) {
+        slot.log_out();
+    }
+    crate::update_tray(app).await;
+    let _ = app.emit(
+        "account-updated",
+        AccountUpdate { account_id: account_id.to_string(), logged_in: false, subscription_changed: false, account: None },
+    );
+    publish_status(app, account_id).await;
+}
+
+// Re-reads the account's credentials, at startup, whenever they change and
+// after signing in from the app. A new login may be a different Claude
+// account, so the profile is fetched again along with usage
+pub async fn check_credentials(app: &AppHandle, account: &AccountSettings) {
+    let state = app.state::<AppState>();
+    match account.credentials.load_token_opt().await {
+        Ok(None) => log_out(app, &account.id).await,
+        Ok(Some(_)) => {
+            if let Some(slot) = state.accounts.lock().await.iter_mut().find(|a| a.id == account.id) {
+                slot.logged_in = true;
+            }
+            sync_profile(app, account).await;
+            let _ = state.usage_service.refresh(app, account).await;
+        }
+        Err(e) => record_error(app, &account.id, e, Failure::Other).await,
+    }
+}
+
+async fn poll(app: AppHandle, account: AccountSettings) {
+    let state = app.state::<AppState>();
+    let mut credentials = CredentialWatcher::new(&account.credentials).await;
+    let fetched = state.usage_service.fetched(&account.id);
+
+    // Initial fetch
+    check_credentials(&app, &account).await;
+    let mut profile_synced = Instant::now();
+
+    loop {
+        // Re-scheduled every cycle so interval changes from settings apply immediately
+        let delay = schedule(&app, &account.id).await;
+        tokio::select! {
+            _ = sleep(delay) => {}
+            _ = state.poll_interval_changed.notified() => continue,
+            _ = fetched.notified() => continue,
+            _ = credentials.changed() => {
+                check_credentials(&app, &account).await;
+                profile_synced = Instant::now();
+                continue;
+            }
+        }
+
+        {
+            let mut paused = state.paused_until.lock().await;
+            match *paused {
+                Some(until) if until > Utc::now() => continue,
+                Some(_) => *paused = None,
+                None => {}
+            }
+        }
+
+        let profile_secs = state.settings.lock().await.polling.profile_interval_secs;
+        if profile_synced.elapsed() >= Duration::from_secs(profile_secs) {
+            sync_profile(&app, &account).await;
+            profile_synced = Instant::now();
+        }
+        let _ = state.usage_service.refresh(&app, &account).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn concurrent_refreshes_share_one_fetch() {
+        let in_flight = InFlight::default();
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let fetch = |result: FetchResult| {
+            let fetches = fetches.clone();
+            async move {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                sleep(Duration::from_millis(50)).await;
+                result
+            }
+        };
+
+        let (first, second) = tokio::join!(
+            in_flight.join("personal", fetch(Ok(()))),
+            in_flight.join("personal", fetch(Err("second fetch ran".to_string()))),
+        );
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert_eq!(first, Ok(()));
+        assert_eq!(second, Ok(()));
+
+        // Done fetches aren't joined, and other accounts fetch on their own
+        let (again, other) = tokio::join!(
+            in_flight.join("personal", fetch(Ok(()))),
+            in_flight.join("work", fetch(Ok(()))),
+        );
+        assert_eq!((again, other), (Ok(()), Ok(())));
+        assert_eq!(fetches.load(Ordering::SeqCst), 3);
+    }
+}
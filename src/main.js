@@ -2,15 +2,22 @@ const { invoke } = window.__TAURI__.core;
 const { listen } = window.__TAURI__.event;
 
 let resetTimes = { fiveHour: null, sevenDay: null };
+let thresholds = { warning: 50, critical: 80 };
 let elements = {};
 let profileModalOpen = false;
+let accounts = [];
+let activeAccount = null;
+// MonitorStatus per account id, from get_status and status-changed
+let statuses = {};
 const COMPACT_HEIGHT = 109;
 const PROFILE_HEADER_HEIGHT = 45;
 const PROFILE_ITEM_HEIGHT = 40;
+const AUTOSTART_PROMPT_HEIGHT = 24;
+const ACCOUNT_ROW_HEIGHT = 16;
 
 function getColorClass(percent) {
-  if (percent >= 80) return 'red';
-  if (percent >= 50) return 'yellow';
+  if (percent >= thresholds.critical) return 'red';
+  if (percent >= thresholds.warning) return 'yellow';
   return 'green';
 }
 
@@ -68,6 +75,15 @@ function updateUsage(usage) {
   updateTimers();
 }
 
+// Back to "Loading..." until the account's first usage arrives, so another
+// account's bars and reset times aren't left on screen
+function showLoading() {
+  elements.usageContainer.classList.add('hidden');
+  elements.loading.textContent = 'Loading...';
+  elements.loading.classList.remove('hidden');
+  resetTimes = { fiveHour: null, sevenDay: null };
+}
+
 function updateTimers() {
   if (resetTimes.fiveHour) {
     const timerPercent = getTimerPercent(resetTimes.fiveHour, 5);
@@ -82,44 +98,241 @@ function updateTimers() {
   }
 }
 
+// Main view height for whatever is currently shown in it
+function mainViewHeight() {
+  let height = COMPACT_HEIGHT;
+  if (!document.getElementById('autostart-prompt').classList.contains('hidden')) {
+    height += AUTOSTART_PROMPT_HEIGHT;
+  }
+  if (accounts.length > 1) {
+    height += accounts.length * ACCOUNT_ROW_HEIGHT + 4;
+  }
+  return height;
+}
+
+function percentCell(limit) {
+  const span = document.createElement('span');
+  if (limit && limit.utilization != null) {
+    const percent = Math.round(limit.utilization);
+    span.className = `value ${getColorClass(percent)}`;
+    span.textContent = `${percent}%`;
+  } else {
+    span.className = 'value';
+    span.textContent = '--%';
+  }
+  return span;
+}
+
+// One row per account when more than one is configured; the bars below
+// show the active account
+function renderAccounts() {
+  const list = document.getElementById('account-list');
+  list.innerHTML = '';
+  list.classList.toggle('hidden', accounts.length <= 1);
+  if (accounts.length <= 1) return;
+
+  accounts.forEach(account => {
+    const row = document.createElement('div');
+    row.className = 'account-row' + (account.id === activeAccount ? ' active' : '');
+    if (!account.logged_in) {
+      row.title = 'Not logged in';
+    } else {
+      row.title = account.last_error ? `Error: ${account.last_error}` : (account.account?.email || account.name);
+    }
+
+    const name = document.createElement('span');
+    name.className = 'account-name' + (account.last_error ? ' red' : '');
+    name.textContent = account.name;
+    row.appendChild(name);
+    row.appendChild(percentCell(account.usage?.five_hour));
+    row.appendChild(percentCell(account.usage?.seven_day));
+
+    row.addEventListener('click', async () => {
+      try {
+        await invoke('set_active_account', { accountId: account.id });
+      } catch (e) {
+        showError(String(e));
+      }
+    });
+    list.appendChild(row);
+  });
+}
+
+// Signing in from the app works without Claude Code installed
+function showLoggedOut() {
+  elements.usageContainer.classList.add('hidden');
+  elements.errorContainer.classList.add('hidden');
+  elements.loading.textContent = 'Not logged in to Claude';
+  const signIn = document.createElement('button');
+  signIn.textContent = 'Sign in';
+  signIn.addEventListener('click', async () => {
+    signIn.disabled = true;
+    signIn.textContent = 'Waiting for browser…';
+    try {
+      await invoke('oauth_login');
+    } catch (e) {
+      showError(String(e));
+    }
+    signIn.disabled = false;
+    signIn.textContent = 'Sign in';
+  });
+  elements.loading.appendChild(signIn);
+  elements.loading.classList.remove('hidden');
+}
+
+// Says how old the bars are when they aren't current; the error, if any,
+// is in the tooltip
+function showStatus() {
+  const account = accounts.find(a => a.id === activeAccount);
+  const status = statuses[activeAccount];
+  if (!account || !account.logged_in || !account.usage || !status) return;
+  // e.g. "Offline, data is 14 min old", as in the tray
+  if (status.message) {
+    showError(status.message);
+    elements.errorText.title = status.error || '';
+  }
+}
+
+function showActiveAccount() {
+  const account = accounts.find(a => a.id === activeAccount);
+  if (!account) return;
+  if (!account.logged_in) {
+    showLoggedOut();
+    return;
+  }
+  if (account.usage) {
+    updateUsage(account.usage);
+  } else {
+    showLoading();
+  }
+  if (account.last_error) {
+    showError(account.last_error);
+  } else {
+    elements.errorContainer.classList.add('hidden');
+  }
+  showStatus();
+}
+
+async function loadAccounts() {
+  accounts = await invoke('get_accounts');
+  activeAccount = await invoke('get_active_account');
+  for (const account of accounts) {
+    statuses[account.id] = await invoke('get_status', { accountId: account.id });
+  }
+  renderAccounts();
+  showActiveAccount();
+  if (!profileModalOpen) {
+    await invoke('set_window_height', { height: mainViewHeight() });
+  }
+}
+
+function updateAccount(accountId, changes) {
+  const account = accounts.find(a => a.id === accountId);
+  if (account) Object.assign(account, changes);
+  renderAccounts();
+}
+
 function showError(msg) {
   elements.loading.classList.add('hidden');
   elements.errorContainer.classList.remove('hidden');
   elements.errorText.textContent = msg;
 }
 
+let noticeTimeout = null;
+
+// Brief message in the error area that clears itself
+function showNotice(msg) {
+  showError(msg);
+  clearTimeout(noticeTimeout);
+  noticeTimeout = setTimeout(() => elements.errorContainer.classList.add('hidden'), 4000);
+}
+
+async function openUrl(url) {
+  try {
+    const opened = await invoke('open_url', { url });
+    if (opened.route === 'system_default') {
+      showNotice(`Opened in default browser: ${opened.reason}`);
+    }
+  } catch (e) {
+    showError(String(e));
+  }
+}
+
+function profileRow(name, detail, isSelected, badge, onClick) {
+  const div = document.createElement('div');
+  div.className = 'profile-item' + (isSelected ? ' selected' : '');
+  div.innerHTML = `
+    <div class="profile-header">
+      <div class="profile-name"></div>
+      ${badge ? '<span class="subscription-badge"></span>' : ''}
+    </div>
+    <div class="profile-email"></div>
+  `;
+  div.querySelector('.profile-name').textContent = name;
+  div.querySelector('.profile-email').textContent = detail;
+  if (badge) div.querySelector('.subscription-badge').textContent = badge;
+  div.addEventListener('click', async () => {
+    try {
+      await onClick();
+    } catch (e) {
+      showError(String(e));
+    }
+    hideProfileModal();
+  });
+  return div;
+}
+
+// Picks the profile links open in while the active account is active. Once
+// the account's email is known the choice is stored for that email, so any
+// profile can be used, signed in to Claude or not.
 async function showProfileModal() {
-  const profiles = await invoke('get_chrome_profiles');
-  const selectedProfile = await invoke('get_selected_profile') || 'Default';
+  const profiles = await invoke('get_browser_profiles');
+  const selected = await invoke('get_selected_profile');
   const account = await invoke('get_account');
+  const email = account?.email || null;
+  // A mapping to 'default' keeps the account on the default profile
+  const mapping = email && selected.profiles.find(m =>
+    m.email.toLowerCase() === email.toLowerCase() && m.browser !== 'default');
+  const defaultBrowser = selected.browser || 'chrome';
+  const defaultProfile = selected.profile || 'Default';
+  const selectedBrowser = mapping ? mapping.browser : defaultBrowser;
+  const selectedProfile = mapping ? mapping.profile : defaultProfile;
+
+  document.querySelector('#profile-modal .modal-title').textContent =
+    email ? `Browser profile for ${email}` : 'Select Browser Profile';
 
   const profileList = document.getElementById('profile-list');
   profileList.innerHTML = '';
 
+  if (email) {
+    const fallback = profiles.find(p => p.browser === defaultBrowser && p.id === defaultProfile);
+    profileList.appendChild(profileRow(
+      'Default',
+      fallback ? `${fallback.browser_name} · ${fallback.name}` : defaultBrowser,
+      !mapping,
+      null,
+      () => invoke('clear_selected_profile', { email }),
+    ));
+  }
+
   profiles.forEach(profile => {
-    const div = document.createElement('div');
-    div.className = 'profile-item' + (profile.id === selectedProfile ? ' selected' : '');
+    const isSelected = profile.browser === selectedBrowser && profile.id === selectedProfile;
 
     // Check if this profile matches the logged-in account
-    const isLoggedInProfile = account?.email && profile.email &&
-      profile.email.toLowerCase() === account.email.toLowerCase();
-
-    div.innerHTML = `
-      <div class="profile-header">
-        <div class="profile-name">${profile.name}</div>
-        ${isLoggedInProfile && account.subscription ? `<span class="subscription-badge">${account.subscription}</span>` : ''}
-      </div>
-      ${profile.email ? `<div class="profile-email">${profile.email}</div>` : ''}
-    `;
-    div.addEventListener('click', async () => {
-      await invoke('set_selected_profile', { profileId: profile.id });
-      hideProfileModal();
-    });
-    profileList.appendChild(div);
+    const isLoggedInProfile = email && profile.email &&
+      profile.email.toLowerCase() === email.toLowerCase();
+
+    profileList.appendChild(profileRow(
+      profile.name,
+      profile.email ? `${profile.browser_name} · ${profile.email}` : profile.browser_name,
+      isSelected,
+      isLoggedInProfile ? account.subscription?.label : null,
+      () => invoke('set_selected_profile', { browser: profile.browser, profileId: profile.id, email }),
+    ));
   });
 
-  // Calculate height based on number of profiles
-  const expandedHeight = PROFILE_HEADER_HEIGHT + (profiles.length * PROFILE_ITEM_HEIGHT);
+  // Calculate height based on number of rows
+  const expandedHeight = PROFILE_HEADER_HEIGHT + (profileList.children.length * PROFILE_ITEM_HEIGHT);
   await invoke('set_window_height', { height: expandedHeight });
 
   // Switch views - hide main, show profiles
@@ -134,13 +347,30 @@ async function hideProfileModal() {
   document.getElementById('main-view').classList.remove('hidden');
   profileModalOpen = false;
 
-  // Shrink window back to compact size
-  await invoke('set_window_height', { height: COMPACT_HEIGHT });
+  // Shrink window back to the main view
+  await invoke('set_window_height', { height: mainViewHeight() });
+}
+
+async function answerAutostartPrompt(enabled) {
+  document.getElementById('autostart-prompt').classList.add('hidden');
+  await invoke('set_window_height', { height: mainViewHeight() });
+  try {
+    await invoke('set_autostart', { enabled });
+  } catch (e) {
+    showError(String(e));
+  }
 }
 
 async function checkFirstRun() {
   // Profile is now auto-detected from account email
   // No need to show profile selector on first run
+
+  // Offer launch at login once
+  const autostart = await invoke('get_autostart');
+  if (!autostart.prompted) {
+    document.getElementById('autostart-prompt').classList.remove('hidden');
+    await invoke('set_window_height', { height: mainViewHeight() });
+  }
 }
 
 window.addEventListener('DOMContentLoaded', async () => {
@@ -177,29 +407,67 @@ window.addEventListener('DOMContentLoaded', async () => {
       showProfileModal();
     }
   });
+  document.getElementById('btn-autostart-yes').addEventListener('click', () => answerAutostartPrompt(true));
+  document.getElementById('btn-autostart-no').addEventListener('click', () => answerAutostartPrompt(false));
   document.getElementById('btn-home').addEventListener('click', () => {
-    invoke('open_url', { url: 'https://claude.ai/' });
+    openUrl('https://claude.ai/');
   });
   document.getElementById('btn-settings').addEventListener('click', () => {
-    invoke('open_url', { url: 'https://claude.ai/settings/usage' });
+    openUrl('https://claude.ai/settings/usage');
   });
 
   await listen('usage-updated', (e) => {
-    elements.errorContainer.classList.add('hidden');
-    updateUsage(e.payload);
+    const { account_id, ...usage } = e.payload;
+    updateAccount(account_id, { usage, last_error: null });
+    if (account_id === activeAccount) {
+      elements.errorContainer.classList.add('hidden');
+      updateUsage(usage);
+    }
   });
 
-  await listen('usage-error', (e) => showError(e.payload));
+  await listen('usage-error', (e) => {
+    updateAccount(e.payload.account_id, { last_error: e.payload.error });
+    if (e.payload.account_id === activeAccount) showError(e.payload.error);
+  });
+
+  await listen('status-changed', (e) => {
+    statuses[e.payload.account_id] = e.payload;
+    if (e.payload.account_id === activeAccount) showStatus();
+  });
+
+  await listen('settings-updated', (e) => {
+    thresholds = e.payload.thresholds;
+    // The account list or the active account may have changed
+    loadAccounts();
+  });
+
+  await listen('settings-error', (e) => showError(e.payload));
 
   await listen('account-updated', (e) => {
-    // Account info received - profile auto-selected
+    const { account_id, logged_in, subscription_changed, ...account } = e.payload;
+    if (logged_in) {
+      updateAccount(account_id, { account, logged_in });
+    } else {
+      updateAccount(account_id, { account: null, usage: null, last_error: null, last_updated: null, logged_in });
+    }
+    if (account_id === activeAccount) {
+      showActiveAccount();
+      if (subscription_changed && account.subscription) {
+        showNotice(`Subscription changed to ${account.subscription.label}`);
+      }
+    }
   });
 
   try {
-    const usage = await invoke('get_usage');
-    if (usage) updateUsage(usage);
-    const error = await invoke('get_last_error');
-    if (error) showError(error);
+    const settings = await invoke('get_settings');
+    thresholds = settings.thresholds;
+    await loadAccounts();
+  } catch (e) {}
+
+  // Reported before this window was listening
+  try {
+    const startupErrors = await invoke('get_startup_errors');
+    if (startupErrors.length) showError(startupErrors.join('\n'));
   } catch (e) {}
 
   setTimeout(async () => {
@@ -212,4 +480,6 @@ window.addEventListener('DOMContentLoaded', async () => {
   checkFirstRun();
 
   setInterval(updateTimers, 1000);
+  // Keep "data is N min old" current
+  setInterval(showStatus, 30000);
 });